@@ -1,69 +1,150 @@
-use itertools::Itertools;
-
-fn split_to_vecs_and_sort(lines: Vec<String>) -> (Vec<i32>, Vec<i32>) {
-    let mut left_values: Vec<i32> = Vec::with_capacity(lines.len());
-    let mut right_values: Vec<i32> = Vec::with_capacity(lines.len());
-    for line in lines {
-        let values = line.split("   ").collect::<Vec<&str>>();
-        left_values.push(values[0].parse::<i32>().unwrap());
-        right_values.push(values[1].parse::<i32>().unwrap());
+use std::collections::BTreeMap;
+use std::io::{self, BufRead};
+use std::path::Path;
+
+use crate::utilities::{consume_reader_lines, stream_lines};
+
+// Both location ids of a line, separated by any amount of whitespace. Blank lines are skipped.
+fn parse_line(line: &str) -> Option<(i32, i32)> {
+    let values: Vec<&str> = line.split_whitespace().collect();
+    match values[..] {
+        [] => None,
+        [left, right] => Some((left.parse::<i32>().unwrap(), right.parse::<i32>().unwrap())),
+        _ => panic!("expected two columns: {}", line),
     }
-    left_values.sort();
-    right_values.sort();
-    (left_values, right_values)
 }
 
-fn group_by_and_count(input: Vec<i32>) -> Vec<(i32, usize)> {
-    input
-        .into_iter()
-        .group_by(|key| *key)
-        .into_iter()
-        .map(|(value, group)| (value, group.into_iter().count()))
-        .collect()
+fn parse_columns(lines: &[String]) -> (Vec<i32>, Vec<i32>) {
+    lines.iter().filter_map(|line| parse_line(line)).unzip()
 }
 
-fn part_1(lines: Vec<String>) -> i64 {
-    let (left_values, right_values) = split_to_vecs_and_sort(lines);
+fn total_distance(left_values: &[i32], right_values: &[i32]) -> i64 {
+    LocationCounts::from_columns(left_values, right_values).total_distance()
+}
+
+fn similarity_score(left_values: &[i32], right_values: &[i32]) -> i64 {
+    LocationCounts::from_columns(left_values, right_values).similarity_score()
+}
 
-    left_values
-        .into_iter()
-        .zip(right_values)
-        .map(|(x, y)| (x - y).abs() as i64)
-        .sum::<i64>()
+// How often each location id appears in either list. Both metrics only depend on these counts,
+// so a list is read line by line in memory proportional to the number of distinct ids.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+struct LocationCounts {
+    left: BTreeMap<i32, usize>,
+    right: BTreeMap<i32, usize>,
 }
 
-fn part_2(lines: Vec<String>) -> i64 {
-    let (left_values, right_values) = split_to_vecs_and_sort(lines);
-    let left_group = group_by_and_count(left_values);
-    let right_group = group_by_and_count(right_values);
-
-    left_group
-        .into_iter()
-        .map(|(value, left_count)| {
-            let right_vec = right_group
-                .iter()
-                .filter(|(right_value, _)| *right_value == value)
-                .map(|(_, right_count)| *right_count)
-                .collect::<Vec<usize>>();
-            if right_vec.len() == 1 {
-                ((value as usize) * left_count * right_vec[0]) as i64
-            } else {
-                0_i64
+impl LocationCounts {
+    fn from_columns(left_values: &[i32], right_values: &[i32]) -> Self {
+        let count = |values: &[i32]| {
+            let mut counts: BTreeMap<i32, usize> = BTreeMap::new();
+            for value in values {
+                *counts.entry(*value).or_default() += 1;
             }
-        })
-        .sum()
+            counts
+        };
+        Self {
+            left: count(left_values),
+            right: count(right_values),
+        }
+    }
+
+    fn from_lines<I, S>(lines: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let mut counts = Self::default();
+        for (left, right) in lines
+            .into_iter()
+            .filter_map(|line| parse_line(line.as_ref()))
+        {
+            *counts.left.entry(left).or_default() += 1;
+            *counts.right.entry(right).or_default() += 1;
+        }
+        counts
+    }
+
+    fn from_reader<R: BufRead>(reader: R) -> io::Result<Self> {
+        consume_reader_lines(reader, |lines| Self::from_lines(lines))
+    }
+
+    // Pairs the smallest remaining ids of both lists, a whole run of equal ids at a time
+    fn total_distance(&self) -> i64 {
+        let mut left_runs = self.left.iter().map(|(value, count)| (*value, *count));
+        let mut right_runs = self.right.iter().map(|(value, count)| (*value, *count));
+        let (mut left, mut right) = (left_runs.next(), right_runs.next());
+
+        let mut distance = 0;
+        while let (Some((left_value, left_count)), Some((right_value, right_count))) = (left, right)
+        {
+            let n_pairs = left_count.min(right_count);
+            distance += n_pairs as i64 * (left_value as i64 - right_value as i64).abs();
+            left = match left_count - n_pairs {
+                0 => left_runs.next(),
+                rest => Some((left_value, rest)),
+            };
+            right = match right_count - n_pairs {
+                0 => right_runs.next(),
+                rest => Some((right_value, rest)),
+            };
+        }
+        distance
+    }
+
+    fn similarity_score(&self) -> i64 {
+        self.left
+            .iter()
+            .map(|(value, left_count)| {
+                let right_count = self.right.get(value).copied().unwrap_or_default();
+                *value as i64 * (left_count * right_count) as i64
+            })
+            .sum()
+    }
+}
+
+fn part_1<I, S>(lines: I) -> i64
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+{
+    LocationCounts::from_lines(lines).total_distance()
+}
+
+fn part_2<I, S>(lines: I) -> i64
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+{
+    LocationCounts::from_lines(lines).similarity_score()
+}
+
+fn print_answers(counts: &LocationCounts) {
+    println!("Answer part 1: {}", counts.total_distance());
+    println!("Answer part 2: {}", counts.similarity_score());
 }
 
 pub fn solution(lines: Vec<String>) {
-    println!("Answer part 1: {}", part_1(lines.clone()));
-    println!("Answer part 2: {}", part_2(lines));
+    print_answers(&LocationCounts::from_lines(&lines));
+}
+
+// Streams the input file instead of loading it, solving both parts in a single pass
+pub fn solution_streaming<P: AsRef<Path>>(filename: P) {
+    print_answers(&LocationCounts::from_lines(stream_lines(filename)));
+}
+
+pub fn solution_from_reader<R: BufRead>(reader: R) -> io::Result<()> {
+    print_answers(&LocationCounts::from_reader(reader)?);
+    Ok(())
 }
 
 #[cfg(test)]
 mod q1_tests {
     use crate::utilities::read_lines;
 
-    use super::{part_1, part_2};
+    use std::io::Cursor;
+
+    use super::{parse_columns, part_1, part_2, similarity_score, total_distance, LocationCounts};
 
     fn get_lines() -> Vec<String> {
         read_lines("./data/q1_test.txt")
@@ -78,4 +159,68 @@ mod q1_tests {
     fn test_part2() {
         assert_eq!(part_2(get_lines()), 31_i64);
     }
+
+    #[test]
+    fn test_metrics() {
+        let left = [3, 4, 2, 1, 3, 3];
+        let right = [4, 3, 5, 3, 9, 3];
+        assert_eq!(total_distance(&left, &right), 11);
+        assert_eq!(similarity_score(&left, &right), 31);
+        assert_eq!(total_distance(&[], &[]), 0);
+        assert_eq!(similarity_score(&[7, 7], &[1, 2]), 0);
+    }
+
+    #[test]
+    fn test_tolerant_parser() {
+        let lines: Vec<String> = ["3   4", "4 3", "  2\t5", "", "1    3  ", "3 9", "3\t\t3"]
+            .into_iter()
+            .map(|line| line.to_string())
+            .collect();
+        assert_eq!(
+            parse_columns(&lines),
+            (vec![3, 4, 2, 1, 3, 3], vec![4, 3, 5, 3, 9, 3])
+        );
+        assert_eq!(part_1(lines.clone()), 11);
+        assert_eq!(part_2(lines), 31);
+    }
+
+    #[test]
+    fn test_streaming() {
+        let counts =
+            LocationCounts::from_reader(Cursor::new("3   4\n4   3\n2   5\n1   3\n3   9\n3   3\n"))
+                .unwrap();
+        assert_eq!(counts.total_distance(), 11);
+        assert_eq!(counts.similarity_score(), 31);
+
+        // generated lazily, never held in memory
+        let lines = (0..200_000).map(|i| format!("{}   {}", i % 1000, (i * 7) % 1000));
+        let counts = LocationCounts::from_lines(lines);
+        assert_eq!(counts.left.len(), 1000);
+        assert_eq!(counts.total_distance(), 0);
+
+        let left: Vec<i32> = (0..5000).map(|i| (i * 37) % 101).collect();
+        let right: Vec<i32> = (0..5000).map(|i| (i * 13) % 97 - 20).collect();
+        let lines = left
+            .iter()
+            .zip(right.iter())
+            .map(|(left, right)| format!("{} {}", left, right));
+        let counts = LocationCounts::from_lines(lines);
+        let (mut sorted_left, mut sorted_right) = (left.clone(), right.clone());
+        sorted_left.sort();
+        sorted_right.sort();
+        let distance: i64 = sorted_left
+            .iter()
+            .zip(sorted_right.iter())
+            .map(|(x, y)| (x - y).abs() as i64)
+            .sum();
+        let similarity: i64 = left
+            .iter()
+            .map(|x| *x as i64 * right.iter().filter(|y| *y == x).count() as i64)
+            .sum();
+        assert_eq!(counts.total_distance(), distance);
+        assert_eq!(counts.similarity_score(), similarity);
+
+        // a read error stops the stream and is returned
+        assert!(LocationCounts::from_reader(Cursor::new(b"1 2\n\xff 3\n".to_vec())).is_err());
+    }
 }
@@ -1,6 +1,10 @@
-use std::{hash::Hash, ops::Add, time::Instant};
-
-use itertools::Itertools;
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+    hash::Hash,
+    ops::Add,
+    time::Instant,
+};
 
 const DIRECTIONS: [Direction; 4] = [
     Direction::Up,
@@ -9,6 +13,13 @@ const DIRECTIONS: [Direction; 4] = [
     Direction::Left,
 ];
 
+const DIAGONAL_DIRECTIONS: [Direction; 4] = [
+    Direction::UpRight,
+    Direction::DownRight,
+    Direction::DownLeft,
+    Direction::UpLeft,
+];
+
 #[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
 struct Point {
     x: usize,
@@ -26,6 +37,111 @@ enum Direction {
     Right,
     Down,
     Left,
+    UpRight,
+    DownRight,
+    DownLeft,
+    UpLeft,
+}
+
+// Which moves make a trail: every step changes the height by a delta in
+// `min_delta..=max_delta`, trails start at `start_height` and end at `target_height`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct StepRule {
+    min_delta: i32,
+    max_delta: i32,
+    start_height: u32,
+    target_height: u32,
+    diagonal: bool,
+}
+
+impl Default for StepRule {
+    fn default() -> Self {
+        Self {
+            min_delta: 1,
+            max_delta: 1,
+            start_height: 0,
+            target_height: 9,
+            diagonal: false,
+        }
+    }
+}
+
+impl StepRule {
+    fn allows(&self, from: u32, to: u32) -> bool {
+        let delta = to as i64 - from as i64;
+        self.min_delta as i64 <= delta && delta <= self.max_delta as i64
+    }
+
+    fn directions(&self) -> impl Iterator<Item = &'static Direction> {
+        let diagonals: &'static [Direction] = if self.diagonal {
+            &DIAGONAL_DIRECTIONS
+        } else {
+            &[]
+        };
+        DIRECTIONS.iter().chain(diagonals.iter())
+    }
+}
+
+type TopoMap = Vec<Vec<Option<u32>>>;
+
+// Rules the level by level rating cannot handle, `enumerate_trails` still accepts them
+#[derive(Debug, PartialEq, Eq)]
+enum StepRuleError {
+    NotMonotonic { min_delta: i32, max_delta: i32 },
+}
+
+impl fmt::Display for StepRuleError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::NotMonotonic {
+                min_delta,
+                max_delta,
+            } => write!(
+                f,
+                "step rule must strictly climb or strictly descend, got {}..={}",
+                min_delta, max_delta
+            ),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Trailhead {
+    start: Point,
+    score: usize,
+    rating: usize,
+}
+
+// Trail ends reachable from a point, one bit per target height tile. Every set is as wide as
+// the number of ends, even when only a few of them are reachable.
+#[derive(Clone, Debug)]
+struct EndSet {
+    bits: Vec<u64>,
+}
+
+impl EndSet {
+    fn new(n_ends: usize) -> Self {
+        Self {
+            bits: vec![0; n_ends.div_ceil(64)],
+        }
+    }
+
+    fn insert(&mut self, end: usize) {
+        self.bits[end / 64] |= 1 << (end % 64);
+    }
+
+    fn union(&mut self, other: &Self) {
+        for (bits, other_bits) in self.bits.iter_mut().zip(other.bits.iter()) {
+            *bits |= other_bits;
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.bits
+            .iter()
+            .map(|bits| bits.count_ones() as usize)
+            .sum()
+    }
 }
 
 impl Add<&Direction> for Point {
@@ -37,6 +153,10 @@ impl Add<&Direction> for Point {
             Direction::Right => (self.x as i32 + 1, self.y as i32),
             Direction::Down => (self.x as i32, self.y as i32 + 1),
             Direction::Left => (self.x as i32 - 1, self.y as i32),
+            Direction::UpRight => (self.x as i32 + 1, self.y as i32 - 1),
+            Direction::DownRight => (self.x as i32 + 1, self.y as i32 + 1),
+            Direction::DownLeft => (self.x as i32 - 1, self.y as i32 + 1),
+            Direction::UpLeft => (self.x as i32 - 1, self.y as i32 - 1),
         };
 
         if x < 0 || y < 0 {
@@ -46,7 +166,8 @@ impl Add<&Direction> for Point {
     }
 }
 
-fn parse_data(lines: &[String]) -> (Vec<Vec<u32>>, Vec<Point>) {
+// Digits are heights, any other tile (e.g. `.`) is impassable
+fn parse_data(lines: &[String], rule: &StepRule) -> (TopoMap, Vec<Point>) {
     let mut starting_points: Vec<Point> = Vec::new();
     let map = lines
         .iter()
@@ -55,69 +176,180 @@ fn parse_data(lines: &[String]) -> (Vec<Vec<u32>>, Vec<Point>) {
             line.chars()
                 .enumerate()
                 .map(|(x, point)| {
-                    let digit = point.to_digit(10).unwrap();
-                    if digit == 0 {
+                    let digit = point.to_digit(10);
+                    if digit == Some(rule.start_height) {
                         starting_points.push(Point::new(x, y));
                     }
                     digit
                 })
-                .collect::<Vec<u32>>()
+                .collect::<Vec<Option<u32>>>()
         })
         .collect();
 
     (map, starting_points)
 }
 
-fn search_for_hikes(map: &Vec<Vec<u32>>, current_point: Point, current_height: &u32) -> Vec<Point> {
-    let mut ends: Vec<Point> = Vec::new();
-    for direction in DIRECTIONS.iter() {
-        let new_point = match current_point + direction {
-            Some(point) => point,
-            None => continue,
-        };
+fn get_height(map: &TopoMap, point: Point) -> Option<u32> {
+    *map.get(point.y)?.get(point.x)?
+}
+
+fn next_steps<'a>(
+    map: &'a TopoMap,
+    rule: &'a StepRule,
+    point: Point,
+) -> impl Iterator<Item = Point> + 'a {
+    let height = get_height(map, point);
+    rule.directions().filter_map(move |direction| {
+        let new_point = (point + direction)?;
+        let new_height = get_height(map, new_point)?;
+        rule.allows(height?, new_height).then_some(new_point)
+    })
+}
 
-        match map.get(new_point.y) {
-            Some(row) => match row.get(new_point.x) {
-                Some(height) => {
-                    if current_height + 1 == *height {
-                        if *height == 9 {
-                            ends.push(new_point);
-                        }
+// Walks the map level by level from the target height back towards the start height. Every
+// tile combines the trail ends reachable from (score) and the number of trails through
+// (rating) its next steps. Each tile and edge is visited once, but every tile carries a bitset
+// over all trail ends, so time and the memory of the kept levels grow as O(tiles * ends / 64).
+// Levels further away than one step can reach are dropped as soon as they are no longer needed.
+fn rate_trailheads(
+    map: &TopoMap,
+    rule: &StepRule,
+    starting_points: &[Point],
+) -> Result<Vec<Trailhead>, StepRuleError> {
+    if rule.min_delta > rule.max_delta || (rule.min_delta <= 0 && rule.max_delta >= 0) {
+        return Err(StepRuleError::NotMonotonic {
+            min_delta: rule.min_delta,
+            max_delta: rule.max_delta,
+        });
+    }
+    let climbing = rule.min_delta > 0;
 
-                        ends.extend(search_for_hikes(map, new_point, height));
+    let mut levels: BTreeMap<u32, Vec<Point>> = BTreeMap::new();
+    for (y, row) in map.iter().enumerate() {
+        for (x, height) in row.iter().enumerate() {
+            if let Some(height) = height {
+                levels.entry(*height).or_default().push(Point::new(x, y));
+            }
+        }
+    }
+
+    let ends: HashMap<Point, usize> = levels
+        .get(&rule.target_height)
+        .into_iter()
+        .flatten()
+        .enumerate()
+        .map(|(i, point)| (*point, i))
+        .collect();
+    let order: Vec<(&u32, &Vec<Point>)> = if climbing {
+        levels.iter().rev().collect()
+    } else {
+        levels.iter().collect()
+    };
+
+    let mut rated: BTreeMap<u32, HashMap<Point, (EndSet, usize)>> = BTreeMap::new();
+    for (height, level) in order {
+        let mut current: HashMap<Point, (EndSet, usize)> = HashMap::with_capacity(level.len());
+        for point in level {
+            let mut end_set = EndSet::new(ends.len());
+            let mut rating = 0;
+            if let Some(end) = ends.get(point) {
+                end_set.insert(*end);
+                rating = 1;
+            } else {
+                for next_point in next_steps(map, rule, *point) {
+                    let next_height = get_height(map, next_point).unwrap();
+                    if let Some((next_ends, next_rating)) = rated
+                        .get(&next_height)
+                        .and_then(|level| level.get(&next_point))
+                    {
+                        end_set.union(next_ends);
+                        rating += next_rating;
                     }
                 }
-                None => continue,
-            },
-            None => continue,
+            }
+            current.insert(*point, (end_set, rating));
         }
+        rated.insert(*height, current);
+
+        // the next levels can only step onto heights between themselves and `furthest`
+        let reach = if climbing {
+            rule.max_delta
+        } else {
+            rule.min_delta
+        };
+        let furthest = *height as i64 + reach as i64;
+        rated.retain(|kept, _| {
+            let kept = *kept as i64;
+            kept == rule.start_height as i64
+                || (climbing && kept <= furthest)
+                || (!climbing && kept >= furthest)
+        });
     }
 
-    ends
+    Ok(starting_points
+        .iter()
+        .map(|start| {
+            let (score, rating) = get_height(map, *start)
+                .and_then(|height| rated.get(&height)?.get(start))
+                .map_or((0, 0), |(ends, rating)| (ends.len(), *rating));
+            Trailhead {
+                start: *start,
+                score,
+                rating,
+            }
+        })
+        .collect())
 }
 
-fn part_1(lines: &[String]) -> i64 {
-    let (map, starting_points) = parse_data(lines);
+// Lists up to `limit` complete trails from `start`, each as the sequence of visited points
+fn enumerate_trails(map: &TopoMap, rule: &StepRule, start: Point, limit: usize) -> Vec<Vec<Point>> {
+    let mut trails: Vec<Vec<Point>> = Vec::new();
+    let mut stack: Vec<Vec<Point>> = vec![vec![start]];
 
-    let mut n_solutions = 0_usize;
-    for starting_point in starting_points {
-        let all_ends = search_for_hikes(&map, starting_point, &0);
-        n_solutions += all_ends.into_iter().unique().collect::<Vec<Point>>().len();
+    while let Some(trail) = stack.pop() {
+        if trails.len() >= limit {
+            break;
+        }
+        let point = *trail.last().unwrap();
+        if get_height(map, point) == Some(rule.target_height) {
+            trails.push(trail);
+            continue;
+        }
+
+        // rules that allow flat or backward steps can walk in circles, a trail never revisits
+        let next_points: Vec<Point> = next_steps(map, rule, point)
+            .filter(|next_point| !trail.contains(next_point))
+            .collect();
+        for next_point in next_points.into_iter().rev() {
+            let mut next_trail = trail.clone();
+            next_trail.push(next_point);
+            stack.push(next_trail);
+        }
     }
 
-    n_solutions as i64
+    trails
 }
 
-fn part_2(lines: &[String]) -> i64 {
-    let (map, starting_points) = parse_data(lines);
+fn part_1(lines: &[String]) -> i64 {
+    let rule = StepRule::default();
+    let (map, starting_points) = parse_data(lines, &rule);
 
-    let mut n_solutions = 0_usize;
-    for starting_point in starting_points {
-        let all_ends = search_for_hikes(&map, starting_point, &0);
-        n_solutions += all_ends.into_iter().collect::<Vec<Point>>().len();
-    }
+    rate_trailheads(&map, &rule, &starting_points)
+        .unwrap()
+        .iter()
+        .map(|trailhead| trailhead.score)
+        .sum::<usize>() as i64
+}
+
+fn part_2(lines: &[String]) -> i64 {
+    let rule = StepRule::default();
+    let (map, starting_points) = parse_data(lines, &rule);
 
-    n_solutions as i64
+    rate_trailheads(&map, &rule, &starting_points)
+        .unwrap()
+        .iter()
+        .map(|trailhead| trailhead.rating)
+        .sum::<usize>() as i64
 }
 
 pub fn solution(lines: Vec<String>) {
@@ -140,12 +372,41 @@ pub fn solution(lines: Vec<String>) {
 mod q10_tests {
     use crate::utilities::read_lines;
 
-    use super::{part_1, part_2};
+    use super::{
+        enumerate_trails, parse_data, part_1, part_2, rate_trailheads, Point, StepRule,
+        StepRuleError,
+    };
 
     fn get_lines() -> Vec<String> {
         read_lines("./data/q10_test.txt")
     }
 
+    fn get_example() -> Vec<String> {
+        [
+            "89010123", "78121874", "87430965", "96549874", "45678903", "32019012", "01329801",
+            "10456732",
+        ]
+        .iter()
+        .map(|line| line.to_string())
+        .collect()
+    }
+
+    fn to_lines(map: &str) -> Vec<String> {
+        map.split_whitespace()
+            .map(|line| line.to_string())
+            .collect()
+    }
+
+    fn total(lines: &[String], rule: &StepRule) -> (usize, usize) {
+        let (map, starting_points) = parse_data(lines, rule);
+        rate_trailheads(&map, rule, &starting_points)
+            .unwrap()
+            .iter()
+            .fold((0, 0), |(score, rating), trailhead| {
+                (score + trailhead.score, rating + trailhead.rating)
+            })
+    }
+
     #[test]
     fn test_part1() {
         assert_eq!(part_1(&get_lines()), 36);
@@ -155,4 +416,138 @@ mod q10_tests {
     fn test_part2() {
         assert_eq!(part_2(&get_lines()), 81);
     }
+
+    #[test]
+    fn test_rate_trailheads() {
+        let rule = StepRule::default();
+        let (map, starting_points) = parse_data(&get_example(), &rule);
+        let trailheads = rate_trailheads(&map, &rule, &starting_points).unwrap();
+
+        let scores: Vec<usize> = trailheads.iter().map(|t| t.score).collect();
+        let ratings: Vec<usize> = trailheads.iter().map(|t| t.rating).collect();
+        assert_eq!(scores, vec![5, 6, 5, 3, 1, 3, 5, 3, 5]);
+        assert_eq!(ratings, vec![20, 24, 10, 4, 1, 4, 5, 8, 5]);
+    }
+
+    #[test]
+    fn test_enumerate_trails() {
+        let rule = StepRule::default();
+        let (map, starting_points) = parse_data(&get_example(), &rule);
+        let start = starting_points[4];
+
+        let trails = enumerate_trails(&map, &rule, start, 100);
+        assert_eq!(trails.len(), 1);
+        assert_eq!(trails[0].len(), 10);
+        assert_eq!(trails[0][0], start);
+        for (height, point) in trails[0].iter().enumerate() {
+            assert_eq!(map[point.y][point.x], Some(height as u32));
+        }
+
+        assert_eq!(
+            enumerate_trails(&map, &rule, starting_points[1], 100).len(),
+            24
+        );
+        assert_eq!(
+            enumerate_trails(&map, &rule, starting_points[1], 7).len(),
+            7
+        );
+        assert_eq!(
+            enumerate_trails(&map, &rule, Point::new(1, 0), 100),
+            vec![vec![Point::new(1, 0)]]
+        );
+
+        let plateau = StepRule {
+            min_delta: 0,
+            target_height: 2,
+            ..StepRule::default()
+        };
+        let (map, starting_points) = parse_data(&to_lines("0112 .11."), &plateau);
+        let trails = enumerate_trails(&map, &plateau, starting_points[0], 100);
+        assert_eq!(
+            trails.iter().map(|trail| trail.len()).collect::<Vec<_>>(),
+            vec![4, 6]
+        );
+    }
+
+    #[test]
+    fn test_impassable_tiles() {
+        let rule = StepRule::default();
+
+        let lines = to_lines("...0... ...1... ...2... 6543456 7.....7 8.....8 9.....9");
+        assert_eq!(total(&lines, &rule).0, 2);
+
+        let lines = to_lines("..90..9 ...1.98 ...2..7 6543456 765.987 876.... 987....");
+        assert_eq!(total(&lines, &rule).0, 4);
+
+        let lines = to_lines("10..9.. 2...8.. 3...7.. 4567654 ...8..3 ...9..2 .....01");
+        assert_eq!(total(&lines, &rule).0, 3);
+
+        let lines = to_lines(".....0. ..4321. ..5..2. ..6543. ..7..4. ..8765. ..9....");
+        assert_eq!(total(&lines, &rule).1, 3);
+    }
+
+    #[test]
+    fn test_step_rule_variants() {
+        let descending = StepRule {
+            min_delta: -1,
+            max_delta: -1,
+            start_height: 9,
+            target_height: 0,
+            diagonal: false,
+        };
+        assert_eq!(total(&get_example(), &descending).1, 81);
+
+        let lines = to_lines("0.. .1. ..2");
+        let short = StepRule {
+            target_height: 2,
+            ..StepRule::default()
+        };
+        assert_eq!(total(&lines, &short), (0, 0));
+        let diagonal = StepRule {
+            diagonal: true,
+            ..short
+        };
+        assert_eq!(total(&lines, &diagonal), (1, 1));
+
+        let lines = to_lines("02 13");
+        let skipping = StepRule {
+            min_delta: 1,
+            max_delta: 2,
+            target_height: 3,
+            ..StepRule::default()
+        };
+        assert_eq!(total(&lines, &skipping), (1, 2));
+        let single = StepRule {
+            max_delta: 1,
+            ..skipping
+        };
+        assert_eq!(total(&lines, &single), (0, 0));
+    }
+
+    #[test]
+    fn test_step_rule_must_be_monotonic() {
+        let (map, starting_points) = parse_data(&get_example(), &StepRule::default());
+        for (min_delta, max_delta) in [(-1, 1), (0, 1), (-1, 0), (2, 1)] {
+            let rule = StepRule {
+                min_delta,
+                max_delta,
+                ..StepRule::default()
+            };
+            assert_eq!(
+                rate_trailheads(&map, &rule, &starting_points),
+                Err(StepRuleError::NotMonotonic {
+                    min_delta,
+                    max_delta
+                })
+            );
+        }
+        assert_eq!(
+            StepRuleError::NotMonotonic {
+                min_delta: 0,
+                max_delta: 1
+            }
+            .to_string(),
+            "step rule must strictly climb or strictly descend, got 0..=1"
+        );
+    }
 }
@@ -30,7 +30,7 @@ fn let_magic_happen(
     } else {
         let stone_len = stone.ilog10() + 1;
 
-        if stone_len % 2 == 0 {
+        if stone_len.is_multiple_of(2) {
             let half_stone_len = stone_len / 2;
             let value = 10_usize.pow(half_stone_len);
             let stone_1 = stone / value;
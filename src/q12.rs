@@ -1,7 +1,14 @@
+use image::{ImageResult, Rgb, RgbImage};
 use std::collections::HashSet;
+use std::fs;
 use std::hash::Hash;
+use std::io;
+use std::path::Path;
 use std::time::Instant;
 
+const FENCE_COLOUR: Rgb<u8> = Rgb([0, 0, 0]);
+const CORNER_COLOUR: Rgb<u8> = Rgb([255, 0, 0]);
+
 const DIRECTIONS: [Direction; 4] = [
     Direction::Up,
     Direction::Right,
@@ -48,73 +55,163 @@ impl Point {
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
 struct Corner(Point);
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct BoundingBox {
+    min: Point,
+    max: Point,
+}
+
+#[derive(Clone, Debug)]
+struct Region {
+    plant: char,
+    cells: HashSet<Point>,
+    area: usize,
+    perimeter: usize,
+    sides: usize,
+    bounding_box: BoundingBox,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum PriceKind {
+    Perimeter,
+    Sides,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct RegionReport {
+    id: usize,
+    plant: char,
+    area: usize,
+    perimeter: usize,
+    sides: usize,
+    price: usize,
+    bulk_price: usize,
+}
+
+// Every region of the garden plus the index of the region each plot belongs to
+#[derive(Clone, Debug)]
+struct RegionMap {
+    labels: Vec<Vec<usize>>,
+    regions: Vec<Region>,
+}
+
 impl Corner {
     fn new(point: Point) -> Self {
         Self(point)
     }
 }
 
-fn field_sizes(
-    garden: &Vec<Vec<char>>,
-    visited_fields: &mut HashSet<(char, Point)>,
-    plant_fields: &mut HashSet<Point>,
-    current_point: Point,
-    plant: &char,
-    max_x: &i32,
-    max_y: &i32,
-) -> (usize, usize) {
-    if visited_fields.contains(&(*plant, current_point)) {
-        return (0, 0);
+impl BoundingBox {
+    fn new(point: Point) -> Self {
+        Self {
+            min: point,
+            max: point,
+        }
+    }
+
+    fn extend(&mut self, point: Point) {
+        self.min = Point::new(self.min.x.min(point.x), self.min.y.min(point.y));
+        self.max = Point::new(self.max.x.max(point.x), self.max.y.max(point.y));
+    }
+
+    fn width(&self) -> usize {
+        self.max.x - self.min.x + 1
+    }
+
+    fn height(&self) -> usize {
+        self.max.y - self.min.y + 1
+    }
+}
+
+impl Region {
+    fn price(&self) -> usize {
+        self.area * self.perimeter
     }
-    if plant != &garden[current_point.y][current_point.x] {
-        return (0, 1);
+
+    fn bulk_price(&self) -> usize {
+        self.area * self.sides
     }
-    visited_fields.insert((*plant, current_point));
-    plant_fields.insert(current_point);
-
-    let mut total_fields = 1_usize;
-    let mut total_fences = 0_usize;
-
-    for direction in DIRECTIONS {
-        match current_point.next_point(direction, max_x, max_y) {
-            Some(new_point) => {
-                let (n_fields, n_fences) = field_sizes(
-                    garden,
-                    visited_fields,
-                    plant_fields,
-                    new_point,
-                    plant,
-                    max_x,
-                    max_y,
-                );
-                total_fields += n_fields;
-                total_fences += n_fences;
-            }
-            None => {
-                total_fences += 1;
+}
+
+fn parse_garden(lines: &[String]) -> Vec<Vec<char>> {
+    lines
+        .iter()
+        .map(|line| line.chars().collect::<Vec<char>>())
+        .collect()
+}
+
+// Labels connected plots of the same plant with an explicit stack, so region size is not
+// limited by the call stack.
+fn find_regions(garden: &[Vec<char>]) -> RegionMap {
+    let max_y = (garden.len() - 1) as i32;
+    let max_x = (garden[0].len() - 1) as i32;
+    let mut labels: Vec<Vec<Option<usize>>> =
+        garden.iter().map(|row| vec![None; row.len()]).collect();
+    let mut regions: Vec<Region> = Vec::new();
+
+    for (y, row) in garden.iter().enumerate() {
+        for (x, plant) in row.iter().enumerate() {
+            if labels[y][x].is_some() {
                 continue;
             }
+            let label = regions.len();
+            let start = Point::new(x, y);
+            let mut cells: HashSet<Point> = HashSet::new();
+            let mut perimeter = 0_usize;
+            let mut bounding_box = BoundingBox::new(start);
+            let mut stack: Vec<Point> = vec![start];
+            labels[y][x] = Some(label);
+
+            while let Some(current_point) = stack.pop() {
+                cells.insert(current_point);
+                bounding_box.extend(current_point);
+
+                for direction in DIRECTIONS {
+                    match current_point.next_point(direction, &max_x, &max_y) {
+                        Some(new_point) if garden[new_point.y][new_point.x] == *plant => {
+                            if labels[new_point.y][new_point.x].is_none() {
+                                labels[new_point.y][new_point.x] = Some(label);
+                                stack.push(new_point);
+                            }
+                        }
+                        _ => perimeter += 1,
+                    }
+                }
+            }
+
+            let sides = count_corners(&cells);
+            regions.push(Region {
+                plant: *plant,
+                area: cells.len(),
+                cells,
+                perimeter,
+                sides,
+                bounding_box,
+            });
         }
     }
 
-    (total_fields, total_fences)
+    RegionMap {
+        labels: labels
+            .into_iter()
+            .map(|row| row.into_iter().map(|label| label.unwrap()).collect())
+            .collect(),
+        regions,
+    }
 }
 
-fn count_corners(
-    plant_fields: HashSet<Point>,
-    current_point: Point,
-    max_x: &i32,
-    max_y: &i32,
-) -> usize {
+// Corners of the region outline together with how many corners each one counts for.
+// A corner point shared diagonally by two plots of the region counts twice.
+fn find_corners(plant_fields: &HashSet<Point>) -> Vec<(Corner, usize)> {
     let mut left_up_corners: HashSet<Corner> = HashSet::new();
     let mut right_up_corners: HashSet<Corner> = HashSet::new();
     let mut left_down_corners: HashSet<Corner> = HashSet::new();
     let mut right_down_corners: HashSet<Corner> = HashSet::new();
 
-    for field in plant_fields.into_iter() {
+    for field in plant_fields.iter() {
         left_up_corners.insert(Corner::new(Point::new(field.x, field.y + 1)));
         right_up_corners.insert(Corner::new(Point::new(field.x + 1, field.y + 1)));
-        left_down_corners.insert(Corner::new(field));
+        left_down_corners.insert(Corner::new(*field));
         right_down_corners.insert(Corner::new(Point::new(field.x + 1, field.y)));
     }
 
@@ -125,7 +222,7 @@ fn count_corners(
     all_corners.extend(&left_up_corners);
     all_corners.extend(&right_down_corners);
 
-    let mut n_corners: usize = 0;
+    let mut corners: Vec<(Corner, usize)> = Vec::new();
 
     for corner in all_corners {
         let in_top_left = left_up_corners.contains(&corner);
@@ -137,96 +234,171 @@ fn count_corners(
             in_top_right as i8 + in_right_down as i8 + in_left_down as i8 + in_top_left as i8;
 
         if total == 1 || total == 3 {
-            n_corners += 1;
+            corners.push((corner, 1));
             continue;
         }
 
         if total == 2 && ((in_right_down && in_top_left) || (in_top_right && in_left_down)) {
-            n_corners += 2;
+            corners.push((corner, 2));
             continue;
         }
     }
 
-    n_corners
+    corners.sort();
+    corners
 }
 
-fn part_1(lines: &[String]) -> i64 {
-    let garden: Vec<Vec<char>> = lines
+// A polygon has as many sides as corners
+fn count_corners(plant_fields: &HashSet<Point>) -> usize {
+    find_corners(plant_fields)
         .iter()
-        .map(|line| line.chars().collect::<Vec<char>>())
-        .collect();
-    let mut visited_fields: HashSet<(char, Point)> = HashSet::new();
-    let mut field_mapping: Vec<(char, (usize, usize))> = Vec::new();
+        .map(|(_, count)| count)
+        .sum()
+}
 
-    let max_y = (lines.len() - 1) as i32;
-    let max_x = (lines[0].len() - 1) as i32;
+fn region_report(region_map: &RegionMap) -> Vec<RegionReport> {
+    region_map
+        .regions
+        .iter()
+        .enumerate()
+        .map(|(id, region)| RegionReport {
+            id,
+            plant: region.plant,
+            area: region.area,
+            perimeter: region.perimeter,
+            sides: region.sides,
+            price: region.price(),
+            bulk_price: region.bulk_price(),
+        })
+        .collect()
+}
 
-    for (y, row) in garden.iter().enumerate() {
-        for (x, plant) in row.iter().enumerate() {
-            let current_point = Point::new(x, y);
-            if visited_fields.contains(&(*plant, current_point)) {
-                continue;
-            }
-            let mut plant_fields: HashSet<Point> = HashSet::new();
-
-            field_mapping.push((
-                *plant,
-                field_sizes(
-                    &garden,
-                    &mut visited_fields,
-                    &mut plant_fields,
-                    current_point,
-                    plant,
-                    &max_x,
-                    &max_y,
-                ),
-            ))
-        }
-    }
+// Most expensive regions first, ties keep the region order
+fn sort_report(report: &mut [RegionReport], kind: PriceKind) {
+    report.sort_by_key(|row| {
+        let price = match kind {
+            PriceKind::Perimeter => row.price,
+            PriceKind::Sides => row.bulk_price,
+        };
+        (std::cmp::Reverse(price), row.id)
+    });
+}
 
-    field_mapping
-        .into_iter()
-        .map(|(_, (x, y))| x * y)
-        .sum::<usize>() as i64
+fn report_to_csv(report: &[RegionReport]) -> String {
+    let mut csv = String::from("id,plant,area,perimeter,sides,price,bulk_price\n");
+    for row in report {
+        csv.push_str(&format!(
+            "{},{},{},{},{},{},{}\n",
+            row.id, row.plant, row.area, row.perimeter, row.sides, row.price, row.bulk_price
+        ));
+    }
+    csv
 }
 
-fn part_2(lines: &[String]) -> i64 {
-    let garden: Vec<Vec<char>> = lines
-        .iter()
-        .map(|line| line.chars().collect::<Vec<char>>())
-        .collect();
-    let mut visited_fields: HashSet<(char, Point)> = HashSet::new();
-    let mut field_mapping: Vec<(char, (usize, usize))> = Vec::new();
+fn save_report_csv<P: AsRef<Path>>(report: &[RegionReport], path: P) -> io::Result<()> {
+    fs::write(path, report_to_csv(report))
+}
 
-    let max_y = (lines.len() - 1) as i32;
-    let max_x = (lines[0].len() - 1) as i32;
+// Spreads the hues with the golden angle so neighbouring labels get clearly different colours
+fn region_colour(label: usize) -> Rgb<u8> {
+    let hue = (label as f64 * 137.507_764) % 360.0;
+    let sector = hue / 60.0;
+    let x = 1.0 - (sector % 2.0 - 1.0).abs();
+    let (r, g, b) = match sector as u32 {
+        0 => (1.0, x, 0.0),
+        1 => (x, 1.0, 0.0),
+        2 => (0.0, 1.0, x),
+        3 => (0.0, x, 1.0),
+        4 => (x, 0.0, 1.0),
+        _ => (1.0, 0.0, x),
+    };
+    let channel = |value: f64| (80.0 + value * 150.0) as u8;
+    Rgb([channel(r), channel(g), channel(b)])
+}
 
-    for (y, row) in garden.iter().enumerate() {
-        for (x, plant) in row.iter().enumerate() {
-            let current_point = Point::new(x, y);
-            let mut plant_fields: HashSet<Point> = HashSet::new();
+// Draws every plot as a `cell_size` square in its region colour, the fences between
+// regions as black lines and the corners counted for the sides as red dots.
+fn render_regions(region_map: &RegionMap, cell_size: u32) -> RgbImage {
+    let labels = &region_map.labels;
+    let height = labels.len() as u32;
+    let width = labels[0].len() as u32;
+    let mut imgbuf = RgbImage::new(width * cell_size + 1, height * cell_size + 1);
+
+    for (y, row) in labels.iter().enumerate() {
+        for (x, label) in row.iter().enumerate() {
+            let (left, top) = (x as u32 * cell_size, y as u32 * cell_size);
+            for dy in 0..=cell_size {
+                for dx in 0..=cell_size {
+                    imgbuf.put_pixel(left + dx, top + dy, region_colour(*label));
+                }
+            }
+        }
+    }
 
-            if visited_fields.contains(&(*plant, current_point)) {
-                continue;
+    for (y, row) in labels.iter().enumerate() {
+        for (x, label) in row.iter().enumerate() {
+            let (left, top) = (x as u32 * cell_size, y as u32 * cell_size);
+            let fence_up = y == 0 || labels[y - 1][x] != *label;
+            let fence_down = y + 1 == labels.len() || labels[y + 1][x] != *label;
+            let fence_left = x == 0 || row[x - 1] != *label;
+            let fence_right = x + 1 == row.len() || row[x + 1] != *label;
+
+            for i in 0..=cell_size {
+                if fence_up {
+                    imgbuf.put_pixel(left + i, top, FENCE_COLOUR);
+                }
+                if fence_down {
+                    imgbuf.put_pixel(left + i, top + cell_size, FENCE_COLOUR);
+                }
+                if fence_left {
+                    imgbuf.put_pixel(left, top + i, FENCE_COLOUR);
+                }
+                if fence_right {
+                    imgbuf.put_pixel(left + cell_size, top + i, FENCE_COLOUR);
+                }
             }
+        }
+    }
 
-            let (field_size, _) = field_sizes(
-                &garden,
-                &mut visited_fields,
-                &mut plant_fields,
-                current_point,
-                plant,
-                &max_x,
-                &max_y,
-            );
-            let n_corners = count_corners(plant_fields, current_point, &max_x, &max_y);
-            field_mapping.push((*plant, (field_size, n_corners)));
+    for region in region_map.regions.iter() {
+        for (Corner(point), _) in find_corners(&region.cells) {
+            let (cx, cy) = (point.x as u32 * cell_size, point.y as u32 * cell_size);
+            for dy in cy.saturating_sub(1)..=(cy + 1).min(imgbuf.height() - 1) {
+                for dx in cx.saturating_sub(1)..=(cx + 1).min(imgbuf.width() - 1) {
+                    imgbuf.put_pixel(dx, dy, CORNER_COLOUR);
+                }
+            }
         }
     }
 
-    field_mapping
-        .into_iter()
-        .map(|(_, (x, y))| x * y)
+    imgbuf
+}
+
+fn save_region_image<P: AsRef<Path>>(
+    region_map: &RegionMap,
+    cell_size: u32,
+    path: P,
+) -> ImageResult<()> {
+    render_regions(region_map, cell_size).save(path)
+}
+
+fn part_1(lines: &[String]) -> i64 {
+    let garden = parse_garden(lines);
+
+    find_regions(&garden)
+        .regions
+        .iter()
+        .map(|region| region.price())
+        .sum::<usize>() as i64
+}
+
+fn part_2(lines: &[String]) -> i64 {
+    let garden = parse_garden(lines);
+
+    find_regions(&garden)
+        .regions
+        .iter()
+        .map(|region| region.bulk_price())
         .sum::<usize>() as i64
 }
 
@@ -250,12 +422,30 @@ pub fn solution(lines: Vec<String>) {
 mod q12_tests {
     use crate::utilities::read_lines;
 
-    use super::{part_1, part_2};
+    use super::{
+        find_corners, find_regions, parse_garden, part_1, part_2, region_report, render_regions,
+        report_to_csv, sort_report, BoundingBox, Corner, Point, PriceKind, CORNER_COLOUR,
+        FENCE_COLOUR,
+    };
 
     fn get_lines() -> Vec<String> {
         read_lines("./data/q12_test.txt")
     }
 
+    fn to_lines(garden: &str) -> Vec<String> {
+        garden
+            .split_whitespace()
+            .map(|line| line.to_string())
+            .collect()
+    }
+
+    fn get_example() -> Vec<String> {
+        to_lines(
+            "RRRRIICCFF RRRRIICCCF VVRRRCCFFF VVRCCCJFFF VVVVCJJCFE \
+             VVIVCCJJEE VVIIICJJEE MIIIIIJJEE MIIISIJEEE MMMISSJEEE",
+        )
+    }
+
     #[test]
     fn test_part1() {
         assert_eq!(part_1(&get_lines()), 1930);
@@ -265,4 +455,110 @@ mod q12_tests {
     fn test_part2() {
         assert_eq!(part_2(&get_lines()), 1206);
     }
+
+    #[test]
+    fn test_examples() {
+        assert_eq!(part_1(&to_lines("AAAA BBCD BBCC EEEC")), 140);
+        assert_eq!(part_2(&to_lines("AAAA BBCD BBCC EEEC")), 80);
+        assert_eq!(part_1(&to_lines("OOOOO OXOXO OOOOO OXOXO OOOOO")), 772);
+        assert_eq!(part_2(&to_lines("OOOOO OXOXO OOOOO OXOXO OOOOO")), 436);
+        assert_eq!(part_2(&to_lines("EEEEE EXXXX EEEEE EXXXX EEEEE")), 236);
+        assert_eq!(
+            part_2(&to_lines("AAAAAA AAABBA AAABBA ABBAAA ABBAAA AAAAAA")),
+            368
+        );
+        assert_eq!(part_1(&get_example()), 1930);
+        assert_eq!(part_2(&get_example()), 1206);
+    }
+
+    #[test]
+    fn test_find_regions() {
+        let garden = parse_garden(&to_lines("AAAA BBCD BBCC EEEC"));
+        let region_map = find_regions(&garden);
+
+        assert_eq!(region_map.regions.len(), 5);
+        assert_eq!(region_map.labels[2][3], region_map.labels[1][2]);
+
+        let a = &region_map.regions[region_map.labels[0][0]];
+        assert_eq!(a.plant, 'A');
+        assert_eq!((a.area, a.perimeter, a.sides), (4, 10, 4));
+        assert_eq!(
+            a.bounding_box,
+            BoundingBox {
+                min: Point::new(0, 0),
+                max: Point::new(3, 0)
+            }
+        );
+
+        let c = &region_map.regions[region_map.labels[1][2]];
+        assert_eq!((c.area, c.perimeter, c.sides), (4, 10, 8));
+        assert!(c.cells.contains(&Point::new(3, 3)));
+        assert_eq!((c.bounding_box.width(), c.bounding_box.height()), (2, 3));
+    }
+
+    #[test]
+    fn test_find_regions_large() {
+        let garden = vec![vec!['A'; 300]; 300];
+        let region_map = find_regions(&garden);
+
+        assert_eq!(region_map.regions.len(), 1);
+        assert_eq!(region_map.regions[0].area, 90_000);
+        assert_eq!(region_map.regions[0].perimeter, 1200);
+        assert_eq!(region_map.regions[0].sides, 4);
+    }
+
+    #[test]
+    fn test_region_report() {
+        let garden = parse_garden(&to_lines("AAAA BBCD BBCC EEEC"));
+        let mut report = region_report(&find_regions(&garden));
+
+        sort_report(&mut report, PriceKind::Perimeter);
+        let plants: Vec<char> = report.iter().map(|row| row.plant).collect();
+        assert_eq!(plants, vec!['A', 'C', 'B', 'E', 'D']);
+
+        sort_report(&mut report, PriceKind::Sides);
+        let plants: Vec<char> = report.iter().map(|row| row.plant).collect();
+        assert_eq!(plants, vec!['C', 'A', 'B', 'E', 'D']);
+
+        let csv = report_to_csv(&report);
+        let mut csv_lines = csv.lines();
+        assert_eq!(
+            csv_lines.next(),
+            Some("id,plant,area,perimeter,sides,price,bulk_price")
+        );
+        assert_eq!(csv_lines.next(), Some("2,C,4,10,8,40,32"));
+        assert_eq!(csv.lines().count(), 6);
+    }
+
+    #[test]
+    fn test_find_corners() {
+        let garden = parse_garden(&to_lines("AB BA"));
+        let region_map = find_regions(&garden);
+        let a = &region_map.regions[region_map.labels[0][0]];
+
+        let corners = find_corners(&a.cells);
+        assert!(corners.contains(&(Corner(Point::new(1, 1)), 1)));
+
+        let mut cells = a.cells.clone();
+        cells.insert(Point::new(1, 1));
+        assert!(find_corners(&cells).contains(&(Corner(Point::new(1, 1)), 2)));
+        assert_eq!(
+            find_corners(&cells).iter().map(|(_, n)| n).sum::<usize>(),
+            8
+        );
+    }
+
+    #[test]
+    fn test_render_regions() {
+        let garden = parse_garden(&to_lines("AAAA BBCD BBCC EEEC"));
+        let region_map = find_regions(&garden);
+        let image = render_regions(&region_map, 10);
+
+        assert_eq!(image.dimensions(), (41, 41));
+        assert_eq!(*image.get_pixel(5, 0), FENCE_COLOUR);
+        assert_eq!(*image.get_pixel(5, 10), FENCE_COLOUR);
+        assert_eq!(*image.get_pixel(0, 0), CORNER_COLOUR);
+        assert_ne!(image.get_pixel(15, 5), image.get_pixel(15, 15));
+        assert_eq!(image.get_pixel(5, 15), image.get_pixel(15, 25));
+    }
 }
@@ -1,141 +1,587 @@
+use std::collections::BTreeMap;
+use std::fmt;
 use std::time::Instant;
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 struct Button {
-    x: usize,
-    y: usize,
+    x: i64,
+    y: i64,
 }
 
 impl Button {
-    fn new(x: usize, y: usize) -> Self {
+    fn new(x: i64, y: i64) -> Self {
         Self { x, y }
     }
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 struct Point {
-    x: usize,
-    y: usize,
+    x: i64,
+    y: i64,
 }
 
 impl Point {
-    fn new(x: usize, y: usize) -> Self {
+    fn new(x: i64, y: i64) -> Self {
         Self { x, y }
     }
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 struct Machine {
-    button_a: Button,
-    button_b: Button,
+    buttons: Vec<Button>,
     prize: Point,
 }
 
+// How a machine is solved: the cost of each button (in button order), an optional cap on
+// the presses of every button and an offset added to both prize coordinates. Without explicit
+// costs button A costs 3 and every other button 1.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+struct SolveOptions {
+    costs: Option<Vec<i128>>,
+    max_presses: Option<i128>,
+    offset: i64,
+}
+
+impl SolveOptions {
+    fn with_costs(mut self, costs: Vec<i128>) -> Self {
+        self.costs = Some(costs);
+        self
+    }
+
+    fn with_max_presses(mut self, max_presses: i128) -> Self {
+        self.max_presses = Some(max_presses);
+        self
+    }
+
+    fn with_offset(mut self, offset: i64) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    fn costs(&self, n_buttons: usize) -> Result<Vec<i128>, SolveError> {
+        match &self.costs {
+            None => Ok((0..n_buttons).map(|i| if i == 0 { 3 } else { 1 }).collect()),
+            Some(costs) if costs.len() != n_buttons => Err(SolveError::CostCount {
+                costs: costs.len(),
+                buttons: n_buttons,
+            }),
+            // a negative cost would reward pressing a button, the cheapest solution is then
+            // no longer the one with the fewest presses
+            Some(costs) => match costs.iter().position(|cost| *cost < 0) {
+                Some(button) => Err(SolveError::NegativeCost { button }),
+                None => Ok(costs.clone()),
+            },
+        }
+    }
+}
+
+// Buttons beyond the first two are enumerated, at most this many combinations of their presses
+const MAX_EXTRA_COMBINATIONS: i128 = 1_000_000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum SolveError {
+    CostCount { costs: usize, buttons: usize },
+    NegativeCost { button: usize },
+    UnboundedPresses { button: usize },
+    SearchTooLarge { combinations: i128 },
+}
+
+impl fmt::Display for SolveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::CostCount { costs, buttons } => {
+                write!(f, "{} costs given for {} buttons", costs, buttons)
+            }
+            Self::NegativeCost { button } => write!(
+                f,
+                "button {} has a negative cost",
+                (b'A' + *button as u8) as char
+            ),
+            Self::UnboundedPresses { button } => write!(
+                f,
+                "button {} moves backwards and needs a press limit",
+                (b'A' + *button as u8) as char
+            ),
+            Self::SearchTooLarge { combinations } => write!(
+                f,
+                "the extra buttons allow over {} combinations of presses, more than {}",
+                combinations, MAX_EXTRA_COMBINATIONS
+            ),
+        }
+    }
+}
+
+type Vector = (i128, i128);
+
 impl Machine {
-    fn new(button_a: Button, button_b: Button, prize: Point) -> Self {
-        Self {
-            button_a,
-            button_b,
+    fn new(buttons: Vec<Button>, prize: Point) -> Self {
+        Self { buttons, prize }
+    }
+
+    // Cheapest number of presses per button reaching the prize. Two buttons are solved
+    // exactly, every further button is enumerated up to its press bound. Machines whose extra
+    // buttons allow more than MAX_EXTRA_COMBINATIONS presses are rejected instead.
+    fn presses(&self, options: &SolveOptions) -> Result<Option<Vec<i128>>, SolveError> {
+        let costs = options.costs(self.buttons.len())?;
+        let buttons: Vec<Vector> = self
+            .buttons
+            .iter()
+            .map(|button| (button.x as i128, button.y as i128))
+            .collect();
+        let prize = (
+            self.prize.x as i128 + options.offset as i128,
+            self.prize.y as i128 + options.offset as i128,
+        );
+
+        // the remaining prize only shrinks, so the bounds at the full prize hold throughout
+        (2..buttons.len()).try_fold(1_i128, |combinations, next| {
+            let n_options = press_bound(&buttons, next, prize, options.max_presses)
+                .ok_or(SolveError::UnboundedPresses { button: next })?
+                + 1;
+            let combinations = combinations.saturating_mul(n_options.max(1));
+            if combinations > MAX_EXTRA_COMBINATIONS {
+                Err(SolveError::SearchTooLarge { combinations })
+            } else {
+                Ok(combinations)
+            }
+        })?;
+
+        let mut presses = vec![0; buttons.len()];
+        let mut best: Option<(i128, Vec<i128>)> = None;
+        search_presses(
+            &buttons,
+            &costs,
+            options.max_presses,
             prize,
-        }
+            0,
+            &mut presses,
+            &mut best,
+        );
+        Ok(best.map(|(_, presses)| presses))
+    }
+
+    fn solve(&self, options: &SolveOptions) -> Result<Option<i128>, SolveError> {
+        let costs = options.costs(self.buttons.len())?;
+        let presses = self.presses(options)?;
+        Ok(presses.map(|presses| {
+            presses
+                .iter()
+                .zip(costs.iter())
+                .map(|(n_presses, cost)| n_presses * cost)
+                .sum::<i128>()
+        }))
     }
+}
 
-    fn solve(&self, offset: usize) -> Option<usize> {
-        let a = self.button_a.x;
-        let b = self.button_a.y;
-        let c = self.prize.x + offset;
-        let d = self.button_b.x;
-        let e = self.button_b.y;
-        let f = self.prize.y + offset;
+fn within_limit(n_presses: i128, max_presses: Option<i128>) -> bool {
+    max_presses.is_none_or(|max_presses| n_presses <= max_presses)
+}
 
-        let le_bottom = (a * e) as i64 - (b * d) as i64;
-        if a == 0 || le_bottom == 0 {
-            return None;
+// Upper bound on the presses of a button before it overshoots the remaining prize. An axis
+// only bounds the presses when no button moves backwards along it; without such an axis or a
+// press limit there is no bound.
+fn press_bound(
+    buttons: &[Vector],
+    next: usize,
+    remaining: Vector,
+    max_presses: Option<i128>,
+) -> Option<i128> {
+    let forward_x = buttons.iter().all(|button| button.0 >= 0);
+    let forward_y = buttons.iter().all(|button| button.1 >= 0);
+    let (x, y) = buttons[next];
+    let bound = [(forward_x, x, remaining.0), (forward_y, y, remaining.1)]
+        .into_iter()
+        .filter(|&(forward, step, _)| forward && step > 0)
+        .map(|(_, step, remaining)| remaining.div_euclid(step))
+        .min();
+    let bound = match (bound, (x, y)) {
+        (_, (0, 0)) => Some(0),
+        (Some(bound), _) => Some(bound),
+        (None, _) => max_presses,
+    }?;
+    Some(max_presses.map_or(bound, |max_presses| bound.min(max_presses)))
+}
+
+fn search_presses(
+    buttons: &[Vector],
+    costs: &[i128],
+    max_presses: Option<i128>,
+    remaining: Vector,
+    next: usize,
+    presses: &mut Vec<i128>,
+    best: &mut Option<(i128, Vec<i128>)>,
+) {
+    let n_base = buttons.len().min(2);
+    if next < n_base {
+        return search_presses(
+            buttons,
+            costs,
+            max_presses,
+            remaining,
+            n_base,
+            presses,
+            best,
+        );
+    }
+
+    if next < buttons.len() {
+        let button = buttons[next];
+        // buttons without a bound are rejected before the search
+        let bound = press_bound(buttons, next, remaining, max_presses).unwrap_or_default();
+        for n_presses in 0..=bound {
+            presses[next] = n_presses;
+            let remaining = (
+                remaining.0 - n_presses * button.0,
+                remaining.1 - n_presses * button.1,
+            );
+            search_presses(
+                buttons,
+                costs,
+                max_presses,
+                remaining,
+                next + 1,
+                presses,
+                best,
+            );
         }
-        let le_top = (a * f) as i64 - (b * c) as i64;
+        presses[next] = 0;
+        return;
+    }
 
-        let n_presses_b = le_top as f64 / le_bottom as f64;
-        if n_presses_b.fract() != 0.0 || n_presses_b < 0.0 {
-            return None;
+    let base = match n_base {
+        0 => (remaining == (0, 0)).then_some((0, 0)),
+        1 => solve_single(buttons[0], remaining)
+            .filter(|n_presses| within_limit(*n_presses, max_presses))
+            .map(|n_presses| (n_presses, 0)),
+        _ => solve_pair(
+            (buttons[0], buttons[1]),
+            remaining,
+            (costs[0], costs[1]),
+            max_presses,
+        ),
+    };
+    let Some((n_presses_a, n_presses_b)) = base else {
+        return;
+    };
+    presses[..n_base].copy_from_slice(&[n_presses_a, n_presses_b][..n_base]);
+
+    let cost: i128 = presses
+        .iter()
+        .zip(costs.iter())
+        .map(|(n_presses, cost)| n_presses * cost)
+        .sum();
+    if best.as_ref().is_none_or(|(best_cost, _)| cost < *best_cost) {
+        *best = Some((cost, presses.clone()));
+    }
+}
+
+fn solve_single(button: Vector, prize: Vector) -> Option<i128> {
+    match button {
+        (0, 0) => (prize == (0, 0)).then_some(0),
+        (x, y) => {
+            let n_presses = if x != 0 { prize.0 / x } else { prize.1 / y };
+            (n_presses >= 0 && (x * n_presses, y * n_presses) == prize).then_some(n_presses)
         }
-        let n_presses_a = (c as f64 - d as f64 * n_presses_b) / a as f64;
-        if n_presses_a.fract() != 0.0 || n_presses_a < 0.0 {
+    }
+}
+
+// Number of presses of two buttons reaching the prize, solved exactly with Cramer's rule.
+// When the buttons move in the same direction the cheapest combination is returned.
+fn solve_pair(
+    (button_a, button_b): (Vector, Vector),
+    prize: Vector,
+    costs: (i128, i128),
+    max_presses: Option<i128>,
+) -> Option<(i128, i128)> {
+    let (ax, ay) = button_a;
+    let (bx, by) = button_b;
+    let (px, py) = prize;
+
+    let determinant = ax * by - ay * bx;
+    if determinant == 0 {
+        return solve_collinear(button_a, button_b, prize, costs, max_presses);
+    }
+
+    let a_top = px * by - py * bx;
+    let b_top = ax * py - ay * px;
+    if a_top % determinant != 0 || b_top % determinant != 0 {
+        return None;
+    }
+    let (n_presses_a, n_presses_b) = (a_top / determinant, b_top / determinant);
+    if n_presses_a < 0 || n_presses_b < 0 {
+        return None;
+    }
+    if !within_limit(n_presses_a, max_presses) || !within_limit(n_presses_b, max_presses) {
+        return None;
+    }
+
+    Some((n_presses_a, n_presses_b))
+}
+
+fn extended_gcd(a: i128, b: i128) -> (i128, i128, i128) {
+    if b == 0 {
+        return (a, 1, 0);
+    }
+    let (gcd, x, y) = extended_gcd(b, a % b);
+    (gcd, y, x - (a / b) * y)
+}
+
+fn div_ceil(numerator: i128, denominator: i128) -> i128 {
+    -(-numerator).div_euclid(denominator)
+}
+
+// Both buttons (and the prize) lie on one line through the origin, so only one axis matters:
+// n_a * a + n_b * b = p. The solutions of this diophantine equation form a line and the cost
+// changes linearly along it, so the cheapest one sits at an end of the non-negative range.
+fn solve_collinear(
+    button_a: Vector,
+    button_b: Vector,
+    prize: Vector,
+    (cost_a, cost_b): (i128, i128),
+    max_presses: Option<i128>,
+) -> Option<(i128, i128)> {
+    let crosses = |(x, y): Vector| x * prize.1 - y * prize.0 != 0;
+    if crosses(button_a) || crosses(button_b) {
+        return None;
+    }
+
+    let pick_axis = |(x, y): Vector| if x != 0 || y != 0 { Some(x != 0) } else { None };
+    let use_x = match (pick_axis(button_a), pick_axis(button_b)) {
+        (Some(use_x), _) | (None, Some(use_x)) => use_x,
+        (None, None) => return (prize == (0, 0)).then_some((0, 0)),
+    };
+    let axis = |(x, y): Vector| if use_x { x } else { y };
+    let (a, b, p) = (axis(button_a), axis(button_b), axis(prize));
+
+    if a == 0 {
+        return (p % b == 0 && within_limit(p / b, max_presses)).then_some((0, p / b));
+    }
+    if b == 0 {
+        return (p % a == 0 && within_limit(p / a, max_presses)).then_some((p / a, 0));
+    }
+
+    let (gcd, x, y) = extended_gcd(a, b);
+    if p % gcd != 0 {
+        return None;
+    }
+    // n_a = a0 + k * step_a, n_b = b0 - k * step_b
+    let (a0, b0) = (x * (p / gcd), y * (p / gcd));
+    let (step_a, step_b) = (b / gcd, a / gcd);
+    let (a_min, a_max) = k_range(a0, step_a, max_presses);
+    let (b_min, b_max) = k_range(b0, -step_b, max_presses);
+    let k_min = a_min.into_iter().chain(b_min).max();
+    let k_max = a_max.into_iter().chain(b_max).min();
+    if let (Some(k_min), Some(k_max)) = (k_min, k_max) {
+        if k_min > k_max {
             return None;
         }
+    }
+
+    let k = match (k_min, k_max) {
+        (Some(k_min), _) if cost_a * step_a - cost_b * step_b > 0 => k_min,
+        (_, Some(k_max)) => k_max,
+        (Some(k_min), None) => k_min,
+        (None, None) => unreachable!("non-negative presses bound at least one end"),
+    };
+    Some((a0 + k * step_a, b0 - k * step_b))
+}
 
-        Some(n_presses_a as usize * 3 + n_presses_b as usize)
+// Range of k keeping n = n0 + k * step within [0, max_presses], open ends are None
+fn k_range(n0: i128, step: i128, max_presses: Option<i128>) -> (Option<i128>, Option<i128>) {
+    let at_least_zero = div_ceil(-n0, step.abs());
+    let at_most_max = max_presses.map(|max_presses| (max_presses - n0).div_euclid(step.abs()));
+    if step > 0 {
+        (Some(at_least_zero), at_most_max)
+    } else {
+        (at_most_max.map(|k| -k), Some(-at_least_zero))
     }
 }
 
-fn parse_line(line: &str, split_str: &str) -> (usize, usize) {
-    let clean_line = line.replace(" ", "");
-    let split_line: Vec<&str> = clean_line.split(",").collect();
-    let x = split_line[0]
-        .split(split_str)
-        .last()
-        .unwrap()
-        .parse::<usize>()
-        .unwrap();
-    let y = split_line[1]
-        .split(split_str)
-        .last()
-        .unwrap()
-        .parse::<usize>()
-        .unwrap();
+#[derive(Debug, PartialEq, Eq)]
+enum ParseError {
+    UnknownLabel {
+        block: usize,
+        line: usize,
+        label: String,
+    },
+    InvalidCoordinates {
+        block: usize,
+        line: usize,
+        text: String,
+    },
+    DuplicateLabel {
+        block: usize,
+        line: usize,
+        label: String,
+    },
+    MissingButton {
+        block: usize,
+        label: String,
+    },
+    MissingPrize {
+        block: usize,
+    },
+}
 
-    (x, y)
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::UnknownLabel { block, line, label } => write!(
+                f,
+                "machine {} (line {}): unknown label `{}`",
+                block, line, label
+            ),
+            Self::InvalidCoordinates { block, line, text } => write!(
+                f,
+                "machine {} (line {}): invalid coordinates `{}`",
+                block, line, text
+            ),
+            Self::DuplicateLabel { block, line, label } => write!(
+                f,
+                "machine {} (line {}): `{}` is given twice",
+                block, line, label
+            ),
+            Self::MissingButton { block, label } => {
+                write!(f, "machine {}: missing `Button {}`", block, label)
+            }
+            Self::MissingPrize { block } => write!(f, "machine {}: missing `Prize`", block),
+        }
+    }
 }
 
-fn parse_lines(lines: &[String]) -> Vec<Machine> {
-    let mut button_a = Button::new(0, 0);
-    let mut button_b = Button::new(0, 0);
-    let mut prize = Point::new(0, 0);
-    let mut my_machines: Vec<Machine> = Vec::new();
+// A coordinate is written as `X+94`, `X=8400` or `X-3`
+fn parse_coordinate(text: &str, axis: char) -> Option<i64> {
+    let value = text.trim().strip_prefix(axis)?.trim_start();
+    let value = value
+        .strip_prefix('+')
+        .or_else(|| value.strip_prefix('='))
+        .unwrap_or(value);
+    value.trim().parse::<i64>().ok()
+}
+
+fn parse_coordinates(text: &str) -> Option<(i64, i64)> {
+    let (x, y) = text.split_once(',')?;
+    Some((parse_coordinate(x, 'X')?, parse_coordinate(y, 'Y')?))
+}
 
+// Splits the input into blank-line separated blocks of (line number, line), both 1-based
+fn split_blocks(lines: &[String]) -> Vec<Vec<(usize, &str)>> {
+    let mut blocks = vec![Vec::new()];
     for (i, line) in lines.iter().enumerate() {
-        let modulo = i % 4;
-        if modulo == 3 {
-            my_machines.push(Machine::new(button_a, button_b, prize));
-            continue;
-        }
-        if modulo == 2 {
-            let (x, y) = parse_line(line, "=");
-            prize = Point::new(x, y);
+        if line.trim().is_empty() {
+            if !blocks.last().unwrap().is_empty() {
+                blocks.push(Vec::new());
+            }
             continue;
         }
+        blocks.last_mut().unwrap().push((i + 1, line.trim()));
+    }
+    if blocks.last().unwrap().is_empty() {
+        blocks.pop();
+    }
+    blocks
+}
 
-        let (x, y) = parse_line(line, "+");
+fn parse_block(block: usize, block_lines: &[(usize, &str)]) -> Result<Machine, ParseError> {
+    let mut buttons: BTreeMap<char, Button> = BTreeMap::new();
+    let mut prize = None;
 
-        if modulo == 0 {
-            button_a = Button::new(x, y);
-            continue;
-        }
+    for &(line, text) in block_lines {
+        let (label, values) = text.split_once(':').unwrap_or((text, ""));
+        let label = label.trim();
+        let Some((x, y)) = parse_coordinates(values) else {
+            return Err(ParseError::InvalidCoordinates {
+                block,
+                line,
+                text: values.trim().to_string(),
+            });
+        };
+        let duplicate = || ParseError::DuplicateLabel {
+            block,
+            line,
+            label: label.to_string(),
+        };
 
-        if modulo == 1 {
-            button_b = Button::new(x, y);
+        if label == "Prize" {
+            if prize.replace(Point::new(x, y)).is_some() {
+                return Err(duplicate());
+            }
             continue;
         }
+        let name = label.strip_prefix("Button ").map(str::trim);
+        let letter = match name.map(|name| (name.chars().next(), name.len())) {
+            Some((Some(letter), 1)) if letter.is_ascii_uppercase() => letter,
+            _ => {
+                return Err(ParseError::UnknownLabel {
+                    block,
+                    line,
+                    label: label.to_string(),
+                })
+            }
+        };
+        if buttons.insert(letter, Button::new(x, y)).is_some() {
+            return Err(duplicate());
+        }
     }
-    my_machines.push(Machine::new(button_a, button_b, prize));
-    my_machines
+
+    // buttons are lettered consecutively from A, and every machine has at least A and B
+    let n_buttons = buttons.len().max(2);
+    let buttons = (b'A'..)
+        .take(n_buttons)
+        .map(|letter| {
+            buttons
+                .get(&(letter as char))
+                .copied()
+                .ok_or(ParseError::MissingButton {
+                    block,
+                    label: (letter as char).to_string(),
+                })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let prize = prize.ok_or(ParseError::MissingPrize { block })?;
+
+    Ok(Machine::new(buttons, prize))
+}
+
+fn parse_machines(lines: &[String]) -> Result<Vec<Machine>, ParseError> {
+    split_blocks(lines)
+        .iter()
+        .enumerate()
+        .map(|(i, block_lines)| parse_block(i + 1, block_lines))
+        .collect()
+}
+
+fn parse_lines(lines: &[String]) -> Vec<Machine> {
+    parse_machines(lines).unwrap_or_else(|error| panic!("{}", error))
+}
+
+fn total_cost(machines: &[Machine], options: &SolveOptions) -> i64 {
+    machines
+        .iter()
+        .enumerate()
+        .map(|(i, machine)| {
+            machine
+                .solve(options)
+                .unwrap_or_else(|error| panic!("machine {}: {}", i + 1, error))
+                .unwrap_or_default()
+        })
+        .sum::<i128>()
+        .try_into()
+        .expect("total cost does not fit in an i64")
 }
 
 fn part_1(lines: &[String]) -> i64 {
     let games = parse_lines(lines);
 
-    games
-        .into_iter()
-        .map(|game| game.solve(0).unwrap_or_default())
-        .sum::<usize>() as i64
+    let options = SolveOptions::default().with_max_presses(100);
+    total_cost(&games, &options)
 }
 
 fn part_2(lines: &[String]) -> i64 {
     let games = parse_lines(lines);
 
-    games
-        .into_iter()
-        .map(|game| game.solve(10000000000000).unwrap_or_default())
-        .sum::<usize>() as i64
+    let options = SolveOptions::default().with_offset(10000000000000);
+    total_cost(&games, &options)
 }
 
 pub fn solution(lines: Vec<String>) {
@@ -158,12 +604,43 @@ pub fn solution(lines: Vec<String>) {
 mod q13_tests {
     use crate::utilities::read_lines;
 
-    use super::{part_1, part_2};
+    use super::{
+        parse_lines, parse_machines, part_1, part_2, Button, Machine, ParseError, Point,
+        SolveError, SolveOptions,
+    };
 
     fn get_lines() -> Vec<String> {
         read_lines("./data/q13_test.txt")
     }
 
+    fn get_example() -> Vec<String> {
+        "Button A: X+94, Y+34
+Button B: X+22, Y+67
+Prize: X=8400, Y=5400
+
+Button A: X+26, Y+66
+Button B: X+67, Y+21
+Prize: X=12748, Y=12176
+
+Button A: X+17, Y+86
+Button B: X+84, Y+37
+Prize: X=7870, Y=6450
+
+Button A: X+69, Y+23
+Button B: X+27, Y+71
+Prize: X=18641, Y=10279"
+            .lines()
+            .map(|line| line.to_string())
+            .collect()
+    }
+
+    fn machine(a: (i64, i64), b: (i64, i64), prize: (i64, i64)) -> Machine {
+        Machine::new(
+            vec![Button::new(a.0, a.1), Button::new(b.0, b.1)],
+            Point::new(prize.0, prize.1),
+        )
+    }
+
     #[test]
     fn test_part1() {
         assert_eq!(part_1(&get_lines()), 480);
@@ -173,4 +650,275 @@ mod q13_tests {
     fn test_part2() {
         assert_eq!(part_2(&get_lines()), 875318608908);
     }
+
+    #[test]
+    fn test_example() {
+        assert_eq!(part_1(&get_example()), 480);
+        assert_eq!(part_2(&get_example()), 875318608908);
+
+        let options = SolveOptions::default();
+        let machines = parse_lines(&get_example());
+        assert_eq!(machines[0].presses(&options).unwrap(), Some(vec![80, 40]));
+        assert_eq!(machines[1].presses(&options).unwrap(), None);
+        assert_eq!(
+            machines[1]
+                .presses(&options.with_offset(10000000000000))
+                .unwrap(),
+            Some(vec![118679050709, 103199174542])
+        );
+    }
+
+    #[test]
+    fn test_exact_large_offset() {
+        // the prize is reachable only when 1 + offset is a multiple of 3
+        let machine = machine((3, 0), (0, 3), (1, 1));
+        let options = SolveOptions::default();
+        assert_eq!(
+            machine
+                .presses(&options.clone().with_offset(10000000000000))
+                .unwrap(),
+            None
+        );
+        assert_eq!(
+            machine
+                .presses(&options.with_offset(10000000000001))
+                .unwrap(),
+            Some(vec![3333333333334, 3333333333334])
+        );
+    }
+
+    #[test]
+    fn test_collinear() {
+        let options = SolveOptions::default();
+        assert_eq!(
+            machine((2, 2), (1, 1), (10, 10)).presses(&options).unwrap(),
+            Some(vec![0, 10])
+        );
+        assert_eq!(
+            machine((4, 4), (6, 6), (16, 16)).presses(&options).unwrap(),
+            Some(vec![1, 2])
+        );
+        assert_eq!(
+            machine((1, 1), (6, 6), (16, 16)).presses(&options).unwrap(),
+            Some(vec![4, 2])
+        );
+        assert_eq!(
+            machine((4, 4), (6, 6), (15, 15)).presses(&options).unwrap(),
+            None
+        );
+        assert_eq!(
+            machine((4, 4), (6, 6), (2, 2)).presses(&options).unwrap(),
+            None
+        );
+        assert_eq!(
+            machine((2, 2), (1, 1), (10, 11)).presses(&options).unwrap(),
+            None
+        );
+        assert_eq!(
+            machine((0, 2), (0, 3), (0, 7)).presses(&options).unwrap(),
+            Some(vec![2, 1])
+        );
+        assert_eq!(
+            machine((0, 0), (1, 1), (5, 5)).presses(&options).unwrap(),
+            Some(vec![0, 5])
+        );
+        assert_eq!(
+            machine((0, 0), (0, 0), (0, 0)).presses(&options).unwrap(),
+            Some(vec![0, 0])
+        );
+        assert_eq!(
+            machine((0, 0), (0, 0), (1, 0)).presses(&options).unwrap(),
+            None
+        );
+        assert_eq!(
+            machine((2, 2), (-1, -1), (4, 4)).presses(&options).unwrap(),
+            Some(vec![2, 0])
+        );
+        assert_eq!(
+            machine((-2, -2), (-1, -1), (4, 4))
+                .presses(&options)
+                .unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn test_press_limit() {
+        let separate = machine((1, 0), (0, 1), (101, 5));
+        let options = SolveOptions::default();
+
+        assert_eq!(separate.solve(&options).unwrap(), Some(308));
+        assert_eq!(
+            separate.solve(&options.with_max_presses(100)).unwrap(),
+            None
+        );
+
+        let collinear = machine((1, 1), (2, 2), (10, 10));
+        let options = SolveOptions::default().with_costs(vec![1, 3]);
+        assert_eq!(collinear.presses(&options).unwrap(), Some(vec![10, 0]));
+        assert_eq!(
+            collinear
+                .presses(&options.clone().with_max_presses(6))
+                .unwrap(),
+            Some(vec![6, 2])
+        );
+        assert_eq!(
+            collinear.presses(&options.with_max_presses(3)).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn test_costs() {
+        let options = SolveOptions::default().with_costs(vec![1, 1]);
+
+        assert_eq!(
+            machine((94, 34), (22, 67), (8400, 5400))
+                .solve(&options)
+                .unwrap(),
+            Some(120)
+        );
+    }
+
+    #[test]
+    fn test_more_buttons() {
+        let buttons = vec![Button::new(1, 0), Button::new(0, 1), Button::new(1, 1)];
+        let machine = Machine::new(buttons, Point::new(5, 7));
+
+        let options = SolveOptions::default().with_costs(vec![1, 1, 1]);
+        assert_eq!(machine.presses(&options).unwrap(), Some(vec![0, 2, 5]));
+
+        let options = SolveOptions::default().with_costs(vec![1, 1, 3]);
+        assert_eq!(machine.presses(&options).unwrap(), Some(vec![5, 7, 0]));
+
+        let options = SolveOptions::default()
+            .with_costs(vec![1, 1, 1])
+            .with_max_presses(4);
+        assert_eq!(machine.presses(&options).unwrap(), Some(vec![1, 3, 4]));
+
+        // without explicit costs button A costs 3 and the others 1
+        assert_eq!(machine.solve(&SolveOptions::default()).unwrap(), Some(7));
+        assert_eq!(
+            machine.solve(&SolveOptions::default().with_costs(vec![1, 1])),
+            Err(SolveError::CostCount {
+                costs: 2,
+                buttons: 3
+            })
+        );
+        assert_eq!(
+            machine.solve(&SolveOptions::default().with_costs(vec![1, -1, 1])),
+            Err(SolveError::NegativeCost { button: 1 })
+        );
+        assert_eq!(
+            SolveError::NegativeCost { button: 1 }.to_string(),
+            "button B has a negative cost"
+        );
+        assert!(matches!(
+            machine.solve(&SolveOptions::default().with_offset(10000000000000)),
+            Err(SolveError::SearchTooLarge { .. })
+        ));
+        assert_eq!(
+            part_1(&to_lines(
+                "Button A: X+1, Y+0\nButton B: X+0, Y+1\nButton C: X+1, Y+1\nPrize: X=5, Y=7"
+            )),
+            7
+        );
+
+        let single = Machine::new(vec![Button::new(2, 3)], Point::new(8, 12));
+        let options = SolveOptions::default().with_costs(vec![2]);
+        assert_eq!(single.solve(&options).unwrap(), Some(8));
+    }
+
+    fn to_lines(text: &str) -> Vec<String> {
+        text.lines().map(|line| line.to_string()).collect()
+    }
+
+    #[test]
+    fn test_parse_layout() {
+        let lines = to_lines(
+            "
+
+Prize: X=8400, Y=5400
+  Button B: X+22, Y+67
+Button A:X+94,Y+34
+
+
+
+Button A: X-3, Y+0
+Button B: X=1, Y=-1
+Button C: X+1, Y+1
+Prize: X=-4, Y=1
+
+
+",
+        );
+        let machines = parse_machines(&lines).unwrap();
+        assert_eq!(machines.len(), 2);
+        assert_eq!(machines[0], machine((94, 34), (22, 67), (8400, 5400)));
+        assert_eq!(
+            machines[1],
+            Machine::new(
+                vec![Button::new(-3, 0), Button::new(1, -1), Button::new(1, 1)],
+                Point::new(-4, 1)
+            )
+        );
+
+        let options = SolveOptions::default()
+            .with_costs(vec![3, 1, 1])
+            .with_max_presses(5);
+        assert_eq!(machines[1].presses(&options).unwrap(), Some(vec![3, 2, 3]));
+        assert_eq!(
+            machines[1].presses(&SolveOptions::default()),
+            Err(SolveError::UnboundedPresses { button: 2 })
+        );
+        assert_eq!(
+            SolveError::UnboundedPresses { button: 2 }.to_string(),
+            "button C moves backwards and needs a press limit"
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let error = |text: &str| parse_machines(&to_lines(text)).unwrap_err();
+
+        assert_eq!(
+            error("Button A: X+1, Y+1\nButton B: X+1, Y+2\nPrize: X=1, Y=1\n\nButton A: X+1, Y+1\nPrize: X=1, Y=1"),
+            ParseError::MissingButton {
+                block: 2,
+                label: "B".to_string()
+            }
+        );
+        assert_eq!(
+            error("Button A: X+1, Y+1\nButton B: X+1, Y+2"),
+            ParseError::MissingPrize { block: 1 }
+        );
+        assert_eq!(
+            error("Button A: X+1, Y+1\nButton A: X+1, Y+2"),
+            ParseError::DuplicateLabel {
+                block: 1,
+                line: 2,
+                label: "Button A".to_string()
+            }
+        );
+        assert_eq!(
+            error("Button A: X+1, Y+1\nButton B: X+1\nPrize: X=1, Y=1"),
+            ParseError::InvalidCoordinates {
+                block: 1,
+                line: 2,
+                text: "X+1".to_string()
+            }
+        );
+        assert_eq!(
+            error("Button A: X+1, Y+1\nButton B: X+1, Y+2\nGoal: X=1, Y=1"),
+            ParseError::UnknownLabel {
+                block: 1,
+                line: 3,
+                label: "Goal".to_string()
+            }
+        );
+        assert_eq!(
+            error("Button A: X+1, Y+1\nButton B: X+1, Y+2\nGoal: X=1, Y=1").to_string(),
+            "machine 1 (line 3): unknown label `Goal`"
+        );
+    }
 }
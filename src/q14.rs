@@ -1,22 +1,26 @@
 use regex::Regex;
 use std::cmp::Ordering;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io;
+use std::ops::Range;
+use std::path::Path;
 use std::time::Instant;
 
 #[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
-struct Point {
+pub struct Point {
     x: i32,
     y: i32,
 }
 
 #[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
-struct Velocity {
+pub struct Velocity {
     x: i32,
     y: i32,
 }
 
 #[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
-struct Robot {
+pub struct Robot {
     position: Point,
     speed: Velocity,
 }
@@ -33,6 +37,46 @@ impl Velocity {
     }
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Room {
+    width: i32,
+    height: i32,
+}
+
+impl Default for Room {
+    fn default() -> Self {
+        Self::new(101, 103)
+    }
+}
+
+impl Room {
+    pub fn new(width: i32, height: i32) -> Self {
+        Self { width, height }
+    }
+
+    pub fn example() -> Self {
+        Self::new(11, 7)
+    }
+
+    // Seconds after which every robot is back at its starting position
+    fn period(&self) -> i32 {
+        let (mut a, mut b) = (self.width, self.height);
+        while b != 0 {
+            (a, b) = (b, a % b);
+        }
+        self.width / a * self.height
+    }
+
+    // Which half of an axis a position lies in; odd sizes have a middle line in neither half
+    fn half(position: i32, size: i32) -> Option<Ordering> {
+        match position.cmp(&(size / 2)) {
+            Ordering::Equal if size % 2 == 1 => None,
+            Ordering::Less => Some(Ordering::Less),
+            _ => Some(Ordering::Greater),
+        }
+    }
+}
+
 impl Robot {
     fn new(point: Point, speed: Velocity) -> Self {
         Self {
@@ -41,44 +85,35 @@ impl Robot {
         }
     }
 
-    fn position_in_n_seconds(&mut self, n_seconds: i32, max_x: i32, max_y: i32) -> Self {
-        let mut new_x = (self.position.x + self.speed.x * n_seconds) % (max_x + 1);
-        new_x = if new_x < 0 { new_x + max_x + 1 } else { new_x };
-        let mut new_y = (self.position.y + self.speed.y * n_seconds) % (max_y + 1);
-        new_y = if new_y < 0 { new_y + max_y + 1 } else { new_y };
-        let new_point = Point::new(new_x, new_y);
-        self.position = new_point;
+    fn position_in_n_seconds(&mut self, n_seconds: i32, room: &Room) -> Self {
+        let new_x = (self.position.x + self.speed.x * n_seconds).rem_euclid(room.width);
+        let new_y = (self.position.y + self.speed.y * n_seconds).rem_euclid(room.height);
+        self.position = Point::new(new_x, new_y);
         *self
     }
 
-    fn get_quadrant(&self, max_x: i32, max_y: i32) -> Option<Quadrant> {
-        let mid_x = max_x / 2;
-        let mid_y = max_y / 2;
-
-        match self.position.x.cmp(&mid_x) {
-            Ordering::Greater => match self.position.y.cmp(&mid_y) {
-                Ordering::Less => Some(Quadrant::TopLeft),
-                Ordering::Greater => Some(Quadrant::BottomLeft),
-                Ordering::Equal => None,
-            },
-            Ordering::Less => match self.position.y.cmp(&mid_y) {
-                Ordering::Less => Some(Quadrant::TopRight),
-                Ordering::Greater => Some(Quadrant::BottomRight),
-                Ordering::Equal => None,
-            },
-            Ordering::Equal => None,
+    pub fn get_quadrant(&self, room: &Room) -> Option<Quadrant> {
+        let half_x = Room::half(self.position.x, room.width)?;
+        let half_y = Room::half(self.position.y, room.height)?;
+
+        match (half_x, half_y) {
+            (Ordering::Less, Ordering::Less) => Some(Quadrant::TopLeft),
+            (Ordering::Less, _) => Some(Quadrant::BottomLeft),
+            (_, Ordering::Less) => Some(Quadrant::TopRight),
+            _ => Some(Quadrant::BottomRight),
         }
     }
 }
 
-enum Quadrant {
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Quadrant {
     TopLeft,
     TopRight,
     BottomLeft,
     BottomRight,
 }
 
-fn parse_data(lines: &[String]) -> Vec<Robot> {
+pub fn parse_data(lines: &[String]) -> Vec<Robot> {
     lines
         .iter()
         .map(|line| {
@@ -95,43 +130,127 @@ fn parse_data(lines: &[String]) -> Vec<Robot> {
         .collect()
 }
 
-fn count_robots_in_quadrant(robots: &Vec<Robot>, max_x: i32, max_y: i32) -> i32 {
-    let mut top_left = 0;
-    let mut top_right = 0;
-    let mut bot_right = 0;
-    let mut bot_left = 0;
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct QuadrantCounts {
+    pub top_left: usize,
+    pub top_right: usize,
+    pub bottom_left: usize,
+    pub bottom_right: usize,
+}
+
+impl QuadrantCounts {
+    pub fn safety_factor(&self) -> i64 {
+        (self.top_left * self.top_right * self.bottom_left * self.bottom_right) as i64
+    }
+}
+
+pub fn count_quadrants(robots: &[Robot], room: &Room) -> QuadrantCounts {
+    let mut counts = QuadrantCounts::default();
 
     for robot in robots {
-        match robot.get_quadrant(max_x, max_y) {
-            Some(Quadrant::TopLeft) => top_left += 1,
-            Some(Quadrant::TopRight) => top_right += 1,
-            Some(Quadrant::BottomLeft) => bot_left += 1,
-            Some(Quadrant::BottomRight) => bot_right += 1,
+        match robot.get_quadrant(room) {
+            Some(Quadrant::TopLeft) => counts.top_left += 1,
+            Some(Quadrant::TopRight) => counts.top_right += 1,
+            Some(Quadrant::BottomLeft) => counts.bottom_left += 1,
+            Some(Quadrant::BottomRight) => counts.bottom_right += 1,
             None => continue,
         }
     }
-    top_left * top_right * bot_right * bot_left
+    counts
 }
 
-fn part_1(lines: &[String]) -> i64 {
+// The room as drawn in the puzzle: the number of robots on each tile, `.` for an empty tile
+// and `+` for more than nine robots. With `split` the middle lines of odd-sized rooms are left
+// blank to show the quadrants.
+pub fn render_room(robots: &[Robot], room: &Room, split: bool) -> String {
+    let mut counts = vec![vec![0; room.width as usize]; room.height as usize];
+    for robot in robots {
+        counts[robot.position.y as usize][robot.position.x as usize] += 1;
+    }
+
+    let mut picture = String::new();
+    for (y, row) in counts.iter().enumerate() {
+        let middle_row = Room::half(y as i32, room.height).is_none();
+        for (x, &count) in row.iter().enumerate() {
+            let middle = middle_row || Room::half(x as i32, room.width).is_none();
+            picture.push(match count {
+                _ if split && middle => ' ',
+                0 => '.',
+                1..=9 => char::from_digit(count, 10).unwrap(),
+                _ => '+',
+            });
+        }
+        picture.push('\n');
+    }
+    picture
+}
+
+fn safety_factor(robots: &[Robot], room: &Room, n_seconds: i32) -> i64 {
+    let moved_robots = move_robots(robots, n_seconds, room);
+    count_quadrants(&moved_robots, room).safety_factor()
+}
+
+fn part_1(lines: &[String], room: &Room, n_seconds: i32) -> i64 {
     let robots = parse_data(lines);
 
-    let max_x = 100;
-    let max_y = 102;
-    let n_seconds = 100;
+    safety_factor(&robots, room, n_seconds)
+}
 
-    let moved_robots: Vec<Robot> = robots
-        .clone()
-        .into_iter()
-        .map(|mut robot| robot.position_in_n_seconds(n_seconds, max_x, max_y))
-        .collect();
+pub fn move_robots(robots: &[Robot], n_seconds: i32, room: &Room) -> Vec<Robot> {
+    robots
+        .iter()
+        .copied()
+        .map(|mut robot| robot.position_in_n_seconds(n_seconds, room))
+        .collect()
+}
+
+fn variance(values: &[i32]) -> f64 {
+    let n = values.len() as f64;
+    let mean = values.iter().map(|&value| value as f64).sum::<f64>() / n;
+    values
+        .iter()
+        .map(|&value| (value as f64 - mean).powi(2))
+        .sum::<f64>()
+        / n
+}
 
-    count_robots_in_quadrant(&moved_robots, max_x, max_y) as i64
+// Each axis moves independently and repeats after the room size, so the second at which the
+// robots bunch up the most along one axis is found within a single period of that axis
+fn min_variance_second(robots: &[Robot], period: i32, axis: fn(&Robot) -> (i32, i32)) -> i32 {
+    let spread = |n_seconds: i32| {
+        let values: Vec<i32> = robots
+            .iter()
+            .map(|robot| {
+                let (position, speed) = axis(robot);
+                (position + speed * n_seconds).rem_euclid(period)
+            })
+            .collect();
+        variance(&values)
+    };
+
+    (0..period)
+        .map(|n_seconds| (n_seconds, spread(n_seconds)))
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+        .unwrap()
+        .0
 }
 
-fn generate_image(robots: &Vec<Robot>, max_x: i32, max_y: i32, iteration: i32) {
+// The tree is drawn when the robots are bunched up on both axes at once. The seconds of
+// least variance per axis are combined with the chinese remainder theorem; there is no
+// such second when the room dimensions share a factor and the two seconds disagree.
+fn find_tree(robots: &[Robot], room: &Room) -> Option<i32> {
+    let (width, height) = (room.width, room.height);
+    let second_x = min_variance_second(robots, width, |robot| (robot.position.x, robot.speed.x));
+    let second_y = min_variance_second(robots, height, |robot| (robot.position.y, robot.speed.y));
+
+    (0..height)
+        .map(|k| second_x + k * width)
+        .find(|n_seconds| n_seconds % height == second_y)
+}
+
+fn save_frame<P: AsRef<Path>>(robots: &[Robot], room: &Room, path: P) -> image::ImageResult<()> {
     let mut imgbuf: image::ImageBuffer<image::Rgb<u8>, Vec<u8>> =
-        image::ImageBuffer::new(max_x as u32 + 1, max_y as u32 + 1);
+        image::ImageBuffer::new(room.width as u32, room.height as u32);
     for pixel in imgbuf.pixels_mut() {
         *pixel = image::Rgb([254, 254, 254]);
     }
@@ -141,50 +260,128 @@ fn generate_image(robots: &Vec<Robot>, max_x: i32, max_y: i32, iteration: i32) {
         *pixel = image::Rgb([0, 0, 0]);
     }
 
-    imgbuf.save(format!("pictures/{}.png", iteration)).unwrap();
+    imgbuf.save(path)
 }
 
-fn part_2(lines: &[String]) -> i64 {
-    let robots = parse_data(lines);
-    let mut robots_map: HashSet<Vec<Robot>> = HashSet::new();
-    robots_map.insert(robots.clone());
+// Writes `<n_seconds>.png` into the directory for every second of the range, to look for
+// the tree by eye
+fn save_frames<P: AsRef<Path>>(
+    robots: &[Robot],
+    room: &Room,
+    seconds: Range<i32>,
+    directory: P,
+) -> image::ImageResult<()> {
+    fs::create_dir_all(&directory)?;
+    for n_seconds in seconds {
+        let moved_robots = move_robots(robots, n_seconds, room);
+        let path = directory.as_ref().join(format!("{}.png", n_seconds));
+        save_frame(&moved_robots, room, path)?;
+    }
+    Ok(())
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct SwarmStats {
+    n_seconds: i32,
+    safety_factor: i64,
+    variance_x: f64,
+    variance_y: f64,
+    // robots sharing their tile with at least one other robot
+    overlapping: usize,
+    // groups of occupied tiles connected horizontally or vertically
+    clusters: usize,
+}
 
-    let max_x = 100;
-    let max_y = 102;
-    let mut n_seconds = 1;
+fn count_overlapping(robots: &[Robot]) -> usize {
+    let mut robots_per_tile: HashMap<Point, usize> = HashMap::new();
+    for robot in robots {
+        *robots_per_tile.entry(robot.position).or_default() += 1;
+    }
+    robots_per_tile.values().filter(|&&count| count > 1).sum()
+}
 
-    loop {
-        let moved_robots: Vec<Robot> = robots
-            .clone()
-            .into_iter()
-            .map(|mut robot| robot.position_in_n_seconds(n_seconds, max_x, max_y))
-            .collect();
-        generate_image(&moved_robots, max_x, max_y, n_seconds);
-        n_seconds += 1;
-
-        match robots_map.insert(moved_robots) {
-            true => continue,
-            false => {
-                println!("{}", n_seconds);
-                break;
+fn count_clusters(robots: &[Robot]) -> usize {
+    let mut unvisited: HashSet<Point> = robots.iter().map(|robot| robot.position).collect();
+    let mut clusters = 0;
+
+    while let Some(&start) = unvisited.iter().next() {
+        clusters += 1;
+        unvisited.remove(&start);
+        let mut stack = vec![start];
+        while let Some(point) = stack.pop() {
+            for (dx, dy) in [(0, -1), (1, 0), (0, 1), (-1, 0)] {
+                let neighbour = Point::new(point.x + dx, point.y + dy);
+                if unvisited.remove(&neighbour) {
+                    stack.push(neighbour);
+                }
             }
         }
     }
+    clusters
+}
+
+fn swarm_stats(robots: &[Robot], room: &Room, n_seconds: i32) -> SwarmStats {
+    let moved_robots = move_robots(robots, n_seconds, room);
+    let xs: Vec<i32> = moved_robots.iter().map(|robot| robot.position.x).collect();
+    let ys: Vec<i32> = moved_robots.iter().map(|robot| robot.position.y).collect();
+
+    SwarmStats {
+        n_seconds,
+        safety_factor: count_quadrants(&moved_robots, room).safety_factor(),
+        variance_x: variance(&xs),
+        variance_y: variance(&ys),
+        overlapping: count_overlapping(&moved_robots),
+        clusters: count_clusters(&moved_robots),
+    }
+}
+
+// Statistics for every second of a full cycle of the swarm
+fn swarm_series(robots: &[Robot], room: &Room) -> Vec<SwarmStats> {
+    (0..room.period())
+        .map(|n_seconds| swarm_stats(robots, room, n_seconds))
+        .collect()
+}
+
+fn series_to_csv(series: &[SwarmStats]) -> String {
+    let mut csv =
+        String::from("n_seconds,safety_factor,variance_x,variance_y,overlapping,clusters\n");
+    for row in series {
+        csv.push_str(&format!(
+            "{},{},{:.3},{:.3},{},{}\n",
+            row.n_seconds,
+            row.safety_factor,
+            row.variance_x,
+            row.variance_y,
+            row.overlapping,
+            row.clusters
+        ));
+    }
+    csv
+}
+
+fn save_series_csv<P: AsRef<Path>>(series: &[SwarmStats], path: P) -> io::Result<()> {
+    fs::write(path, series_to_csv(series))
+}
+
+fn part_2(lines: &[String], room: &Room) -> i64 {
+    let robots = parse_data(lines);
 
-    0
+    find_tree(&robots, room).expect("no second with a tree") as i64
 }
 
 pub fn solution(lines: Vec<String>) {
+    let room = Room::default();
+
     println!("Part 1:");
     let start = Instant::now();
-    let result_1 = part_1(&lines);
+    let result_1 = part_1(&lines, &room, 100);
     let end = Instant::now();
     println!("result: {}", result_1);
     println!("duration: {:?}", end - start);
 
     println!("Part 2:");
     let start = Instant::now();
-    let result_2 = part_2(&lines);
+    let result_2 = part_2(&lines, &room);
     let end = Instant::now();
     println!("result: {}", result_2);
     println!("duration: {:?}", end - start);
@@ -194,7 +391,14 @@ pub fn solution(lines: Vec<String>) {
 mod q14_tests {
     use crate::utilities::read_lines;
 
-    use super::{part_1, part_2};
+    use std::fs;
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    use super::{
+        count_clusters, count_overlapping, count_quadrants, find_tree, move_robots, parse_data,
+        part_1, part_2, render_room, safety_factor, save_frames, series_to_csv, swarm_series,
+        Point, Quadrant, QuadrantCounts, Robot, Room, Velocity,
+    };
 
     fn get_lines() -> Vec<String> {
         read_lines("./data/q14_test.txt")
@@ -202,11 +406,205 @@ mod q14_tests {
 
     #[test]
     fn test_part1() {
-        assert_eq!(part_1(&get_lines()), 12);
+        assert_eq!(part_1(&get_lines(), &Room::example(), 100), 12);
     }
 
     #[test]
     fn test_part2() {
-        assert_eq!(part_2(&get_lines()), 0);
+        let lines: Vec<String> = clustered_robots(7138)
+            .iter()
+            .map(|robot| {
+                format!(
+                    "p={},{} v={},{}",
+                    robot.position.x, robot.position.y, robot.speed.x, robot.speed.y
+                )
+            })
+            .collect();
+        assert_eq!(part_2(&lines, &Room::default()), 7138);
+    }
+
+    fn get_example() -> Vec<String> {
+        "p=0,4 v=3,-3
+p=6,3 v=-1,-3
+p=10,3 v=-1,2
+p=2,0 v=2,-1
+p=0,0 v=1,3
+p=3,0 v=-2,-2
+p=7,6 v=-1,-3
+p=3,0 v=-1,-2
+p=9,3 v=2,3
+p=7,3 v=-1,2
+p=2,4 v=2,-3
+p=9,5 v=-3,-3"
+            .lines()
+            .map(|line| line.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_example_room() {
+        let robots = parse_data(&get_example());
+        assert_eq!(part_1(&get_example(), &Room::example(), 100), 12);
+        assert_eq!(part_1(&get_example(), &Room::default(), 100), 21);
+        assert_eq!(safety_factor(&robots, &Room::example(), 100), 12);
+        assert_eq!(safety_factor(&robots, &Room::example(), 0), 0);
+
+        let mut robot = parse_data(&["p=2,4 v=2,-3".to_string()])[0];
+        let positions: Vec<(i32, i32)> = (1..=5)
+            .map(|_| robot.position_in_n_seconds(1, &Room::example()).position)
+            .map(|position| (position.x, position.y))
+            .collect();
+        assert_eq!(positions, vec![(4, 1), (6, 5), (8, 2), (10, 6), (1, 3)]);
+    }
+
+    #[test]
+    fn test_quadrant_sizes() {
+        let quadrant = |x, y, room: &Room| {
+            Robot::new(Point::new(x, y), Velocity::new(0, 0)).get_quadrant(room)
+        };
+        let odd = Room::new(5, 3);
+        assert!(quadrant(2, 0, &odd).is_none());
+        assert!(quadrant(0, 1, &odd).is_none());
+        assert!(quadrant(1, 0, &odd).is_some());
+
+        // even sizes split evenly without a middle line
+        let even = Room::new(4, 6);
+        let n_quadrants = |room: &Room| {
+            (0..room.width)
+                .flat_map(|x| (0..room.height).map(move |y| (x, y)))
+                .filter_map(|(x, y)| quadrant(x, y, room))
+                .count()
+        };
+        assert_eq!(n_quadrants(&even), 24);
+        assert_eq!(n_quadrants(&odd), 8);
+        assert_eq!(quadrant(1, 2, &even), quadrant(0, 0, &even));
+        assert_ne!(quadrant(2, 3, &even), quadrant(1, 2, &even));
+    }
+
+    // Robots that all end up in a 5x5 block after `n_seconds`
+    fn clustered_robots(n_seconds: i32) -> Vec<Robot> {
+        (0..100)
+            .map(|i| {
+                let speed = Velocity::new((i * 37) % 199 - 99, (i * 53) % 197 - 98);
+                let x = (40 + i % 5 - speed.x * n_seconds).rem_euclid(101);
+                let y = (60 + (i / 5) % 5 - speed.y * n_seconds).rem_euclid(103);
+                Robot::new(Point::new(x, y), speed)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_find_tree() {
+        for n_seconds in [0, 1, 100, 7138, 10402] {
+            let robots = clustered_robots(n_seconds);
+            assert_eq!(find_tree(&robots, &Room::default()), Some(n_seconds));
+        }
+        // 10 and 12 share a factor, so bunching up at second 1 in x and 0 in y never coincides
+        let robots = parse_data(&["p=0,0 v=1,1".to_string(), "p=9,0 v=2,3".to_string()]);
+        assert_eq!(find_tree(&robots, &Room::new(10, 12)), None);
+    }
+
+    #[test]
+    fn test_save_frames() {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let directory =
+            std::env::temp_dir().join(format!("q14_frames_{}_{}", std::process::id(), nanos));
+        let robots = clustered_robots(2);
+        save_frames(&robots, &Room::default(), 1..4, &directory).unwrap();
+
+        for n_seconds in 1..4 {
+            let image = image::open(directory.join(format!("{}.png", n_seconds))).unwrap();
+            assert_eq!((image.width(), image.height()), (101, 103));
+        }
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn test_swarm_series() {
+        let robots = parse_data(&get_example());
+        let room = Room::example();
+        let series = swarm_series(&robots, &room);
+        assert_eq!(series.len(), 77);
+        assert_eq!(Room::new(4, 6).period(), 12);
+        assert_eq!(Room::default().period(), 10403);
+
+        assert_eq!(series[100 % 77].safety_factor, 12);
+        assert_eq!(series[0].overlapping, 2);
+        assert!(series.iter().all(|row| row.clusters <= robots.len()));
+
+        let csv = series_to_csv(&series[..2]);
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(
+            lines[0],
+            "n_seconds,safety_factor,variance_x,variance_y,overlapping,clusters"
+        );
+        assert_eq!(lines.len(), 3);
+        assert!(lines[1].starts_with("0,"));
+    }
+
+    #[test]
+    fn test_overlap_and_clusters() {
+        let robots: Vec<Robot> = [(0, 0), (1, 0), (1, 1), (1, 1), (3, 3), (4, 3), (2, 5)]
+            .into_iter()
+            .map(|(x, y)| Robot::new(Point::new(x, y), Velocity::new(0, 0)))
+            .collect();
+        assert_eq!(count_overlapping(&robots), 2);
+        assert_eq!(count_clusters(&robots), 3);
+    }
+
+    #[test]
+    fn test_quadrants() {
+        let quadrant =
+            |x, y| Robot::new(Point::new(x, y), Velocity::new(0, 0)).get_quadrant(&Room::example());
+        assert_eq!(quadrant(0, 0), Some(Quadrant::TopLeft));
+        assert_eq!(quadrant(10, 0), Some(Quadrant::TopRight));
+        assert_eq!(quadrant(0, 6), Some(Quadrant::BottomLeft));
+        assert_eq!(quadrant(10, 6), Some(Quadrant::BottomRight));
+        assert_eq!(quadrant(5, 0), None);
+
+        let robots = move_robots(&parse_data(&get_example()), 100, &Room::example());
+        let counts = count_quadrants(&robots, &Room::example());
+        assert_eq!(
+            counts,
+            QuadrantCounts {
+                top_left: 1,
+                top_right: 3,
+                bottom_left: 4,
+                bottom_right: 1,
+            }
+        );
+        assert_eq!(counts.safety_factor(), 12);
+    }
+
+    #[test]
+    fn test_render_room() {
+        let robots = parse_data(&get_example());
+        assert_eq!(
+            render_room(&robots, &Room::example(), false),
+            "1.12.......
+...........
+...........
+......11.11
+1.1........
+.........1.
+.......1...
+"
+        );
+
+        let robots = move_robots(&robots, 100, &Room::example());
+        assert_eq!(
+            render_room(&robots, &Room::example(), true),
+            "..... 2..1.
+..... .....
+1.... .....
+           
+..... .....
+...12 .....
+.1... 1....
+"
+        );
     }
 }
@@ -1,17 +1,200 @@
+use num_bigint::BigUint;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::str::FromStr;
 use std::time::Instant;
 
+// Value stored in the A, B and C registers. Division by a power of two is done with shifts,
+// so a shift wider than the register yields 0 instead of overflowing.
+trait Register: Clone + fmt::Debug + fmt::Display + PartialEq + FromStr {
+    fn from_small(value: usize) -> Self;
+    fn is_zero(&self) -> bool;
+    fn low_bits(&self) -> usize;
+    fn shr(&self, shift: &Self) -> Self;
+    fn xor(&self, other: &Self) -> Self;
+    // (self << shift) | digit, or None if a set bit would be shifted out
+    fn push_digit(&self, shift: u32, digit: usize) -> Option<Self>;
+}
+
+macro_rules! impl_register {
+    ($($t:ty),*) => {
+        $(
+            impl Register for $t {
+                fn from_small(value: usize) -> Self {
+                    value as $t
+                }
+
+                fn is_zero(&self) -> bool {
+                    *self == 0
+                }
+
+                fn low_bits(&self) -> usize {
+                    (*self % 8) as usize
+                }
+
+                fn shr(&self, shift: &Self) -> Self {
+                    u32::try_from(*shift)
+                        .ok()
+                        .and_then(|shift| self.checked_shr(shift))
+                        .unwrap_or(0)
+                }
+
+                fn xor(&self, other: &Self) -> Self {
+                    self ^ other
+                }
+
+                fn push_digit(&self, shift: u32, digit: usize) -> Option<Self> {
+                    if self.leading_zeros() < shift {
+                        return None;
+                    }
+                    Some((self << shift) | digit as $t)
+                }
+            }
+        )*
+    };
+}
+
+impl_register!(u64, u128);
+
+impl Register for BigUint {
+    fn from_small(value: usize) -> Self {
+        BigUint::from(value)
+    }
+
+    fn is_zero(&self) -> bool {
+        self.bits() == 0
+    }
+
+    fn low_bits(&self) -> usize {
+        self.iter_u32_digits().next().unwrap_or(0) as usize % 8
+    }
+
+    fn shr(&self, shift: &Self) -> Self {
+        // a shift that does not fit in a u64 is wider than any value we can hold
+        match u64::try_from(shift) {
+            Ok(shift) if shift < self.bits() => self >> shift,
+            _ => BigUint::ZERO,
+        }
+    }
+
+    fn xor(&self, other: &Self) -> Self {
+        self ^ other
+    }
+
+    fn push_digit(&self, shift: u32, digit: usize) -> Option<Self> {
+        Some((self << shift) | BigUint::from(digit))
+    }
+}
+
 #[derive(Clone, Debug)]
-struct Computer {
-    a: usize,
-    b: usize,
-    c: usize,
+struct Computer<R: Register = u64> {
+    a: R,
+    b: R,
+    c: R,
     program: Vec<usize>,
     intruction_pointer: usize,
     output: Vec<usize>,
+    trace: Vec<TraceEntry<R>>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Registers<R: Register = u64> {
+    a: R,
+    b: R,
+    c: R,
+}
+
+impl<R: Register> fmt::Display for Registers<R> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "A={} B={} C={}", self.a, self.b, self.c)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct TraceEntry<R: Register = u64> {
+    step: usize,
+    instruction: Instruction,
+    before: Registers<R>,
+    after: Registers<R>,
+}
+
+impl<R: Register> fmt::Display for TraceEntry<R> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{:>6} {:>3}: {:<6} {} -> {}",
+            self.step,
+            self.instruction.address,
+            self.instruction.to_string(),
+            self.before,
+            self.after
+        )
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum ExecutionError {
+    InvalidOpcode { address: usize, opcode: usize },
+    MissingOperand { address: usize },
+    InvalidComboOperand { address: usize },
+    StepLimitExceeded { steps: usize },
+}
+
+impl fmt::Display for ExecutionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::InvalidOpcode { address, opcode } => {
+                write!(f, "invalid opcode {} at address {}", opcode, address)
+            }
+            Self::MissingOperand { address } => {
+                write!(f, "missing operand at address {}", address)
+            }
+            Self::InvalidComboOperand { address } => {
+                write!(f, "reserved combo operand 7 at address {}", address)
+            }
+            Self::StepLimitExceeded { steps } => {
+                write!(f, "step limit exceeded after {} steps", steps)
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum RunOutcome {
+    Halted,
+    Breakpoint(usize),
+}
+
+#[derive(Clone, Debug, Default)]
+struct RunConfig {
+    max_steps: Option<usize>,
+    breakpoints: HashSet<usize>,
+    trace: bool,
+}
+
+impl RunConfig {
+    fn new() -> Self {
+        Self::default()
+    }
+
+    fn with_max_steps(mut self, max_steps: usize) -> Self {
+        self.max_steps = Some(max_steps);
+        self
+    }
+
+    fn with_breakpoint(mut self, address: usize) -> Self {
+        self.breakpoints.insert(address);
+        self
+    }
+
+    fn with_trace(mut self) -> Self {
+        self.trace = true;
+        self
+    }
 }
 
-impl Computer {
-    fn new(a: usize, b: usize, c: usize, program: Vec<usize>) -> Self {
+impl<R: Register> Computer<R> {
+    fn new(a: R, b: R, c: R, program: Vec<usize>) -> Self {
         Self {
             a,
             b,
@@ -19,102 +202,580 @@ impl Computer {
             program,
             intruction_pointer: 0_usize,
             output: Vec::new(),
+            trace: Vec::new(),
         }
     }
 
-    fn increase_pointer(&mut self) {
-        self.intruction_pointer += 2;
+    fn registers(&self) -> Registers<R> {
+        Registers {
+            a: self.a.clone(),
+            b: self.b.clone(),
+            c: self.c.clone(),
+        }
     }
 
-    fn get_operand(&self) -> usize {
-        self.program[self.intruction_pointer + 1]
+    fn current_instruction(&self) -> Result<Instruction, ExecutionError> {
+        let address = self.intruction_pointer;
+        let code = self.program[address];
+        let opcode = Opcode::from_code(code).ok_or(ExecutionError::InvalidOpcode {
+            address,
+            opcode: code,
+        })?;
+        let raw_operand = *self
+            .program
+            .get(address + 1)
+            .ok_or(ExecutionError::MissingOperand { address })?;
+        let operand = if opcode.takes_combo_operand() {
+            Operand::combo(raw_operand)
+        } else {
+            Operand::literal(raw_operand)
+        };
+
+        Ok(Instruction {
+            address,
+            opcode,
+            operand,
+        })
     }
 
-    fn get_combo_operand(&self, literal_operand: usize) -> usize {
-        match literal_operand {
-            1..=3 => literal_operand,
-            4 => self.a,
-            5 => self.b,
-            6 => self.c,
-            7 => panic!("invalid program"),
-            _ => panic!("impossibru"),
+    fn get_combo_operand(&self, operand: Operand) -> Result<R, ExecutionError> {
+        match operand {
+            Operand::Literal(value) => Ok(R::from_small(value)),
+            Operand::A => Ok(self.a.clone()),
+            Operand::B => Ok(self.b.clone()),
+            Operand::C => Ok(self.c.clone()),
+            Operand::Reserved => Err(ExecutionError::InvalidComboOperand {
+                address: self.intruction_pointer,
+            }),
         }
     }
 
-    fn adv(&mut self, operand: usize) {
-        self.a /= 2_usize.pow(self.get_combo_operand(operand) as u32);
-        self.intruction_pointer += 2;
+    fn divide_a(&self, operand: Operand) -> Result<R, ExecutionError> {
+        let power = self.get_combo_operand(operand)?;
+        Ok(self.a.shr(&power))
     }
 
-    fn bxl(&mut self, operand: usize) {
-        self.b ^= operand;
+    fn execute(&mut self, instruction: Instruction) -> Result<(), ExecutionError> {
+        match instruction.opcode {
+            Opcode::Adv => self.a = self.divide_a(instruction.operand)?,
+            Opcode::Bxl => self.b = self.b.xor(&R::from_small(instruction.operand.code())),
+            Opcode::Bst => {
+                let value = self.get_combo_operand(instruction.operand)?.low_bits();
+                self.b = R::from_small(value);
+            }
+            Opcode::Jnz => {
+                if !self.a.is_zero() {
+                    self.intruction_pointer = instruction.operand.code();
+                    return Ok(());
+                }
+            }
+            Opcode::Bxc => self.b = self.b.xor(&self.c),
+            Opcode::Out => {
+                let value = self.get_combo_operand(instruction.operand)?.low_bits();
+                self.output.push(value);
+            }
+            Opcode::Bdv => self.b = self.divide_a(instruction.operand)?,
+            Opcode::Cdv => self.c = self.divide_a(instruction.operand)?,
+        }
         self.intruction_pointer += 2;
+        Ok(())
     }
 
-    fn bst(&mut self, operand: usize) {
-        self.b = self.get_combo_operand(operand) % 8;
-        self.intruction_pointer += 2;
+    fn get_output(&self) -> String {
+        self.output
+            .iter()
+            .map(|x| x.to_string())
+            .collect::<Vec<String>>()
+            .join(",")
+    }
+
+    fn get_trace(&self) -> String {
+        self.trace
+            .iter()
+            .map(|entry| entry.to_string())
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+
+    fn run(&mut self) -> Result<RunOutcome, ExecutionError> {
+        self.run_with(&RunConfig::default())
     }
 
-    fn jnz(&mut self, operand: usize) {
-        if self.a == 0 {
-            self.intruction_pointer += 2;
-            return;
+    // Breakpoints pause before the instruction at their address is executed. The first
+    // instruction of a run never pauses, so calling this again resumes from a breakpoint.
+    fn run_with(&mut self, config: &RunConfig) -> Result<RunOutcome, ExecutionError> {
+        let mut steps = 0;
+
+        while self.intruction_pointer < self.program.len() {
+            if steps > 0 && config.breakpoints.contains(&self.intruction_pointer) {
+                return Ok(RunOutcome::Breakpoint(self.intruction_pointer));
+            }
+            if config.max_steps.is_some_and(|max_steps| steps >= max_steps) {
+                return Err(ExecutionError::StepLimitExceeded { steps });
+            }
+
+            let instruction = self.current_instruction()?;
+            let before = self.registers();
+            self.execute(instruction)?;
+            steps += 1;
+
+            if config.trace {
+                self.trace.push(TraceEntry {
+                    step: self.trace.len(),
+                    instruction,
+                    before,
+                    after: self.registers(),
+                });
+            }
         }
-        self.intruction_pointer = operand;
+
+        Ok(RunOutcome::Halted)
     }
+}
 
-    fn bxc(&mut self) {
-        self.b ^= self.c;
-        self.intruction_pointer += 2;
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Opcode {
+    Adv,
+    Bxl,
+    Bst,
+    Jnz,
+    Bxc,
+    Out,
+    Bdv,
+    Cdv,
+}
+
+impl Opcode {
+    fn from_code(code: usize) -> Option<Self> {
+        match code {
+            0 => Some(Self::Adv),
+            1 => Some(Self::Bxl),
+            2 => Some(Self::Bst),
+            3 => Some(Self::Jnz),
+            4 => Some(Self::Bxc),
+            5 => Some(Self::Out),
+            6 => Some(Self::Bdv),
+            7 => Some(Self::Cdv),
+            _ => None,
+        }
     }
 
-    fn out(&mut self, operand: usize) {
-        self.output.push(self.get_combo_operand(operand) % 8);
-        self.intruction_pointer += 2;
+    fn code(&self) -> usize {
+        match self {
+            Self::Adv => 0,
+            Self::Bxl => 1,
+            Self::Bst => 2,
+            Self::Jnz => 3,
+            Self::Bxc => 4,
+            Self::Out => 5,
+            Self::Bdv => 6,
+            Self::Cdv => 7,
+        }
     }
 
-    fn bdv(&mut self, operand: usize) {
-        self.b = self.a / 2_usize.pow(self.get_combo_operand(operand) as u32);
-        self.intruction_pointer += 2;
+    fn mnemonic(&self) -> &'static str {
+        match self {
+            Self::Adv => "adv",
+            Self::Bxl => "bxl",
+            Self::Bst => "bst",
+            Self::Jnz => "jnz",
+            Self::Bxc => "bxc",
+            Self::Out => "out",
+            Self::Bdv => "bdv",
+            Self::Cdv => "cdv",
+        }
     }
 
-    fn cdv(&mut self, operand: usize) {
-        self.c = self.a / 2_usize.pow(self.get_combo_operand(operand) as u32);
-        self.intruction_pointer += 2;
+    fn from_mnemonic(mnemonic: &str) -> Option<Self> {
+        (0..8)
+            .filter_map(Self::from_code)
+            .find(|opcode| opcode.mnemonic().eq_ignore_ascii_case(mnemonic))
     }
 
-    fn get_output(&self) -> String {
-        self.output
-            .iter()
-            .map(|x| x.to_string())
-            .collect::<Vec<String>>()
-            .join(",")
+    fn takes_combo_operand(&self) -> bool {
+        matches!(
+            self,
+            Self::Adv | Self::Bst | Self::Out | Self::Bdv | Self::Cdv
+        )
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Operand {
+    Literal(usize),
+    A,
+    B,
+    C,
+    Reserved,
+}
+
+impl Operand {
+    fn literal(operand: usize) -> Self {
+        Self::Literal(operand)
+    }
+
+    fn combo(operand: usize) -> Self {
+        match operand {
+            0..=3 => Self::Literal(operand),
+            4 => Self::A,
+            5 => Self::B,
+            6 => Self::C,
+            _ => Self::Reserved,
+        }
+    }
+
+    fn code(&self) -> usize {
+        match self {
+            Self::Literal(value) => *value,
+            Self::A => 4,
+            Self::B => 5,
+            Self::C => 6,
+            Self::Reserved => 7,
+        }
+    }
+}
+
+impl fmt::Display for Operand {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Literal(value) => write!(f, "{}", value),
+            Self::A => write!(f, "A"),
+            Self::B => write!(f, "B"),
+            Self::C => write!(f, "C"),
+            Self::Reserved => write!(f, "7"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Instruction {
+    address: usize,
+    opcode: Opcode,
+    operand: Operand,
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // bxc reads its operand but ignores it, so it is only shown when it is not 0
+        match (self.opcode, self.operand) {
+            (Opcode::Bxc, Operand::Literal(0)) => write!(f, "{}", self.opcode.mnemonic()),
+            _ => write!(f, "{} {}", self.opcode.mnemonic(), self.operand),
+        }
     }
+}
 
-    fn run(&mut self) {
-        loop {
-            match self.program.get(self.intruction_pointer) {
-                Some(0) => self.adv(self.get_operand()),
-                Some(1) => self.bxl(self.get_operand()),
-                Some(2) => self.bst(self.get_operand()),
-                Some(3) => self.jnz(self.get_operand()),
-                Some(4) => self.bxc(),
-                Some(5) => self.out(self.get_operand()),
-                Some(6) => self.bdv(self.get_operand()),
-                Some(7) => self.cdv(self.get_operand()),
-                None => break,
-                _ => panic!("oopsies"),
+// Decodes the whole program, failing on the same malformed instructions `run` would
+fn disassemble(program: &[usize]) -> Result<Vec<Instruction>, ExecutionError> {
+    program
+        .chunks(2)
+        .enumerate()
+        .map(|(i, chunk)| {
+            let address = i * 2;
+            let opcode = Opcode::from_code(chunk[0]).ok_or(ExecutionError::InvalidOpcode {
+                address,
+                opcode: chunk[0],
+            })?;
+            let raw_operand = *chunk
+                .get(1)
+                .ok_or(ExecutionError::MissingOperand { address })?;
+            let operand = if opcode.takes_combo_operand() {
+                Operand::combo(raw_operand)
+            } else {
+                Operand::literal(raw_operand)
+            };
+            Ok(Instruction {
+                address,
+                opcode,
+                operand,
+            })
+        })
+        .collect()
+}
+
+fn listing(program: &[usize]) -> Result<String, ExecutionError> {
+    Ok(disassemble(program)?
+        .iter()
+        .map(|instruction| format!("{:>3}: {}", instruction.address, instruction))
+        .collect::<Vec<String>>()
+        .join("\n"))
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum AssembleError {
+    UnknownMnemonic { line: usize, mnemonic: String },
+    MissingOperand { line: usize },
+    UnexpectedToken { line: usize, token: String },
+    InvalidOperand { line: usize, operand: String },
+    DuplicateLabel { line: usize, label: String },
+    UnknownLabel { line: usize, label: String },
+    JumpOutOfRange { line: usize, target: usize },
+}
+
+impl fmt::Display for AssembleError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::UnknownMnemonic { line, mnemonic } => {
+                write!(f, "line {}: unknown mnemonic `{}`", line, mnemonic)
+            }
+            Self::MissingOperand { line } => write!(f, "line {}: missing operand", line),
+            Self::UnexpectedToken { line, token } => {
+                write!(f, "line {}: unexpected token `{}`", line, token)
+            }
+            Self::InvalidOperand { line, operand } => {
+                write!(f, "line {}: invalid operand `{}`", line, operand)
+            }
+            Self::DuplicateLabel { line, label } => {
+                write!(f, "line {}: label `{}` is already defined", line, label)
             }
+            Self::UnknownLabel { line, label } => {
+                write!(f, "line {}: unknown label `{}`", line, label)
+            }
+            Self::JumpOutOfRange { line, target } => write!(
+                f,
+                "line {}: jump target {} does not fit in a 3-bit operand",
+                line, target
+            ),
         }
     }
 }
 
-fn parse_register(line: &str) -> usize {
-    line.split(" ").last().unwrap().parse::<usize>().unwrap()
+struct SourceInstruction<'a> {
+    line: usize,
+    opcode: Opcode,
+    operand: Option<&'a str>,
+}
+
+fn parse_literal(operand: &str, line: usize) -> Result<usize, AssembleError> {
+    match operand.parse::<usize>() {
+        Ok(value) if value < 8 => Ok(value),
+        _ => Err(AssembleError::InvalidOperand {
+            line,
+            operand: operand.to_string(),
+        }),
+    }
+}
+
+fn parse_combo(operand: &str, line: usize) -> Result<usize, AssembleError> {
+    match operand {
+        "A" | "a" => Ok(4),
+        "B" | "b" => Ok(5),
+        "C" | "c" => Ok(6),
+        // 4..=6 must be written as register names, 7 is kept so invalid programs can be authored
+        _ => match parse_literal(operand, line)? {
+            value @ (0..=3 | 7) => Ok(value),
+            _ => Err(AssembleError::InvalidOperand {
+                line,
+                operand: operand.to_string(),
+            }),
+        },
+    }
 }
 
-fn parse_data(lines: &[String]) -> Computer {
+// Assembles mnemonic source into a program for `Computer::new`.
+// One instruction per line, `;` starts a comment and `name:` defines a label for `jnz`.
+fn assemble(source: &str) -> Result<Vec<usize>, AssembleError> {
+    let mut labels: HashMap<&str, usize> = HashMap::new();
+    let mut instructions: Vec<SourceInstruction> = Vec::new();
+
+    for (i, raw_line) in source.lines().enumerate() {
+        let line = i + 1;
+        let mut code = raw_line.split(';').next().unwrap().trim();
+
+        if let Some((label, rest)) = code.split_once(':') {
+            let label = label.trim();
+            if label.is_empty() || !label.chars().all(|c| c.is_alphanumeric() || c == '_') {
+                return Err(AssembleError::UnexpectedToken {
+                    line,
+                    token: label.to_string(),
+                });
+            }
+            if labels.insert(label, instructions.len() * 2).is_some() {
+                return Err(AssembleError::DuplicateLabel {
+                    line,
+                    label: label.to_string(),
+                });
+            }
+            code = rest.trim();
+        }
+
+        let mut tokens = code.split_whitespace();
+        let Some(mnemonic) = tokens.next() else {
+            continue;
+        };
+        let opcode = Opcode::from_mnemonic(mnemonic).ok_or(AssembleError::UnknownMnemonic {
+            line,
+            mnemonic: mnemonic.to_string(),
+        })?;
+        let operand = tokens.next();
+        if let Some(token) = tokens.next() {
+            return Err(AssembleError::UnexpectedToken {
+                line,
+                token: token.to_string(),
+            });
+        }
+
+        instructions.push(SourceInstruction {
+            line,
+            opcode,
+            operand,
+        });
+    }
+
+    let mut program = Vec::with_capacity(instructions.len() * 2);
+    for instruction in instructions {
+        let line = instruction.line;
+        let operand = match (instruction.opcode, instruction.operand) {
+            (Opcode::Bxc, None) => 0,
+            (_, None) => return Err(AssembleError::MissingOperand { line }),
+            (Opcode::Jnz, Some(operand)) => match labels.get(operand) {
+                Some(&target) if target < 8 => target,
+                Some(&target) => return Err(AssembleError::JumpOutOfRange { line, target }),
+                None if operand.parse::<usize>().is_ok() => parse_literal(operand, line)?,
+                None => {
+                    return Err(AssembleError::UnknownLabel {
+                        line,
+                        label: operand.to_string(),
+                    })
+                }
+            },
+            (opcode, Some(operand)) if opcode.takes_combo_operand() => parse_combo(operand, line)?,
+            (_, Some(operand)) => parse_literal(operand, line)?,
+        };
+        program.push(instruction.opcode.code());
+        program.push(operand);
+    }
+
+    Ok(program)
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Expr {
+    A,
+    B,
+    C,
+    Literal(usize),
+    Invalid,
+    Shr(Box<Expr>, Box<Expr>),
+    Xor(Box<Expr>, Box<Expr>),
+    Mod8(Box<Expr>),
+}
+
+impl Expr {
+    fn shr(value: Expr, shift: Expr) -> Self {
+        match (value, shift) {
+            (value, Self::Literal(0)) => value,
+            (Self::Literal(value), Self::Literal(shift)) => {
+                Self::Literal(value.checked_shr(shift as u32).unwrap_or(0))
+            }
+            (value, shift) => Self::Shr(Box::new(value), Box::new(shift)),
+        }
+    }
+
+    fn xor(left: Expr, right: Expr) -> Self {
+        match (left, right) {
+            (value, Self::Literal(0)) | (Self::Literal(0), value) => value,
+            (Self::Literal(left), Self::Literal(right)) => Self::Literal(left ^ right),
+            (left, right) => Self::Xor(Box::new(left), Box::new(right)),
+        }
+    }
+
+    fn mod8(value: Expr) -> Self {
+        match value {
+            Self::Literal(value) => Self::Literal(value % 8),
+            Self::Mod8(value) => Self::Mod8(value),
+            value => Self::Mod8(Box::new(value)),
+        }
+    }
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::A => write!(f, "A"),
+            Self::B => write!(f, "B"),
+            Self::C => write!(f, "C"),
+            Self::Literal(value) => write!(f, "{}", value),
+            Self::Invalid => write!(f, "<invalid>"),
+            Self::Shr(value, shift) => write!(f, "({} >> {})", value, shift),
+            Self::Xor(left, right) => write!(f, "({} ^ {})", left, right),
+            Self::Mod8(value) => write!(f, "({} % 8)", value),
+        }
+    }
+}
+
+// Symbolic view of one pass through the program, from address 0 up to the first jnz.
+// Every expression is in terms of the register values A, B and C at the start of the pass.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Iteration {
+    outputs: Vec<Expr>,
+    a: Expr,
+    b: Expr,
+    c: Expr,
+    jump_target: Option<usize>,
+}
+
+impl fmt::Display for Iteration {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for output in self.outputs.iter() {
+            writeln!(f, "out {}", output)?;
+        }
+        writeln!(f, "A = {}", self.a)?;
+        writeln!(f, "B = {}", self.b)?;
+        writeln!(f, "C = {}", self.c)?;
+        match self.jump_target {
+            Some(target) => write!(f, "if A != 0 goto {}", target),
+            None => write!(f, "halt"),
+        }
+    }
+}
+
+fn decompile(program: &[usize]) -> Result<Iteration, ExecutionError> {
+    let mut a = Expr::A;
+    let mut b = Expr::B;
+    let mut c = Expr::C;
+    let mut outputs = Vec::new();
+    let mut jump_target = None;
+
+    for instruction in disassemble(program)? {
+        let combo = match instruction.operand {
+            Operand::Literal(value) => Expr::Literal(value),
+            Operand::A => a.clone(),
+            Operand::B => b.clone(),
+            Operand::C => c.clone(),
+            Operand::Reserved => Expr::Invalid,
+        };
+        match instruction.opcode {
+            Opcode::Adv => a = Expr::shr(a, combo),
+            Opcode::Bxl => b = Expr::xor(b, combo),
+            Opcode::Bst => b = Expr::mod8(combo),
+            Opcode::Jnz => {
+                jump_target = Some(instruction.operand.code());
+                break;
+            }
+            Opcode::Bxc => b = Expr::xor(b, c.clone()),
+            Opcode::Out => outputs.push(Expr::mod8(combo)),
+            Opcode::Bdv => b = Expr::shr(a.clone(), combo),
+            Opcode::Cdv => c = Expr::shr(a.clone(), combo),
+        }
+    }
+
+    Ok(Iteration {
+        outputs,
+        a,
+        b,
+        c,
+        jump_target,
+    })
+}
+
+fn parse_register<R: Register>(line: &str) -> R {
+    match line.split(" ").last().unwrap().parse::<R>() {
+        Ok(value) => value,
+        Err(_) => panic!("invalid register value in `{}`", line),
+    }
+}
+
+fn parse_data<R: Register>(lines: &[String]) -> Computer<R> {
     let register_a = parse_register(&lines[0]);
     let register_b = parse_register(&lines[1]);
     let register_c = parse_register(&lines[2]);
@@ -130,57 +791,155 @@ fn parse_data(lines: &[String]) -> Computer {
 }
 
 fn part_1(lines: &[String]) -> String {
-    let mut computer = parse_data(lines);
+    let mut computer = parse_data::<u64>(lines);
 
-    computer.run();
+    computer.run().expect("program failed");
 
     computer.get_output()
 }
 
-fn get_registry_value(offsets: &[usize]) -> usize {
-    let mut value = 0;
-    let len_offsets = offsets.len();
-    for (i, offset) in offsets.iter().enumerate() {
-        value += offset * 8_usize.pow((len_offsets - i) as u32);
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum QuineError {
+    NotSingleLoop,
+    VariableShift(Expr),
+    OutputsPerIteration(usize),
+    RegisterOverflow,
+    NoSolution,
+    Execution(ExecutionError),
+}
+
+impl fmt::Display for QuineError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::NotSingleLoop => write!(
+                f,
+                "program is not a single loop ending in `jnz 0` as its last instruction"
+            ),
+            Self::VariableShift(a) => write!(
+                f,
+                "A is updated to `{}` per iteration, expected `A >> n` with a constant n > 0",
+                a
+            ),
+            Self::OutputsPerIteration(outputs) => write!(
+                f,
+                "loop body outputs {} values per iteration, expected exactly 1",
+                outputs
+            ),
+            Self::RegisterOverflow => write!(f, "candidate A does not fit in the register"),
+            Self::NoSolution => write!(f, "no initial A makes the program output itself"),
+            Self::Execution(error) => write!(f, "program failed: {}", error),
+        }
     }
-    value
 }
 
-fn part_2(lines: &[String]) -> usize {
-    // solution by hand = ((((((((((((((((5*8)+6)*8+0)*8+0)*8+6)*8+4)*8+4)*8+6)*8+7)+0)*8+4)*8+0)*8+2)*8+5)*8+0)*8+5)*8+2
-    let computer = parse_data(lines);
+// Number of bits A is shifted right by per loop iteration. The search below relies on
+// every iteration outputting one value and dropping a constant number of low bits from A.
+fn detect_a_shift(program: &[usize]) -> Result<u32, QuineError> {
+    let instructions = disassemble(program).map_err(QuineError::Execution)?;
+    let jumps: Vec<&Instruction> = instructions
+        .iter()
+        .filter(|instruction| instruction.opcode == Opcode::Jnz)
+        .collect();
+    match (jumps.as_slice(), instructions.last()) {
+        ([jump], Some(last)) if jump == &last && jump.operand == Operand::Literal(0) => {}
+        _ => return Err(QuineError::NotSingleLoop),
+    }
 
-    let mut offset = 0;
-    let mut offsets: Vec<usize> = Vec::new();
-    let max_idx = computer.program.len() - 1;
+    let iteration = decompile(program).map_err(QuineError::Execution)?;
+    if iteration.outputs.len() != 1 {
+        return Err(QuineError::OutputsPerIteration(iteration.outputs.len()));
+    }
+    match &iteration.a {
+        Expr::Shr(value, shift) => match (value.as_ref(), shift.as_ref()) {
+            (Expr::A, Expr::Literal(shift)) => Ok(*shift as u32),
+            _ => Err(QuineError::VariableShift(iteration.a)),
+        },
+        _ => Err(QuineError::VariableShift(iteration.a)),
+    }
+}
 
-    loop {
-        let mut test_computer = computer.clone();
-        let registry_value = get_registry_value(&offsets) + offset;
-        test_computer.a = registry_value;
-        test_computer.run();
+// Builds A from the most significant digit down: a candidate is kept only if the program
+// outputs the matching suffix of itself. Digits are tried in increasing order, so the
+// solutions are found in increasing order as well.
+fn search_quines<R: Register>(
+    computer: &Computer<R>,
+    shift: u32,
+    prefix: R,
+    depth: usize,
+    solutions: &mut Vec<R>,
+    find_all: bool,
+) -> Result<(), QuineError> {
+    let program = &computer.program;
+    let suffix = &program[program.len() - depth - 1..];
+    let config = RunConfig::new().with_max_steps(program.len() / 2 * (depth + 2));
 
-        let offets_len = offsets.len();
-        let a = &computer.program[(max_idx - offets_len)..=max_idx];
+    for digit in 0..(1_usize << shift) {
+        let a = prefix
+            .push_digit(shift, digit)
+            .ok_or(QuineError::RegisterOverflow)?;
+        if a.is_zero() {
+            continue;
+        }
 
-        if test_computer.output == computer.program[(max_idx - offets_len)..=max_idx] {
-            offsets.push(offset);
-            offset = 0;
-            if offsets.len() - 1 == max_idx {
-                break;
-            }
+        let mut test_computer = computer.clone();
+        test_computer.a = a.clone();
+        test_computer
+            .run_with(&config)
+            .map_err(QuineError::Execution)?;
+        if test_computer.output != suffix {
             continue;
         }
 
-        offset += 1;
-        if offset > 7 {
-            let prev_offset = offsets.pop().unwrap();
-            offsets.push(prev_offset + 1);
-            offset = 0;
+        if suffix.len() == program.len() {
+            solutions.push(a);
+        } else {
+            search_quines(computer, shift, a, depth + 1, solutions, find_all)?;
+        }
+        if !find_all && !solutions.is_empty() {
+            break;
         }
     }
 
-    get_registry_value(&offsets) / 8
+    Ok(())
+}
+
+fn quine_solutions<R: Register>(
+    computer: &Computer<R>,
+    find_all: bool,
+) -> Result<Vec<R>, QuineError> {
+    let shift = detect_a_shift(&computer.program)?;
+
+    let mut solutions = Vec::new();
+    search_quines(
+        computer,
+        shift,
+        R::from_small(0),
+        0,
+        &mut solutions,
+        find_all,
+    )?;
+
+    if solutions.is_empty() {
+        return Err(QuineError::NoSolution);
+    }
+    Ok(solutions)
+}
+
+fn find_quine<R: Register>(computer: &Computer<R>) -> Result<R, QuineError> {
+    quine_solutions(computer, false).map(|mut solutions| solutions.swap_remove(0))
+}
+
+fn find_all_quines<R: Register>(computer: &Computer<R>) -> Result<Vec<R>, QuineError> {
+    quine_solutions(computer, true)
+}
+
+fn part_2(lines: &[String]) -> u64 {
+    let computer = parse_data::<u64>(lines);
+
+    match find_quine(&computer) {
+        Ok(a) => a,
+        Err(error) => panic!("{}", error),
+    }
 }
 
 pub fn solution(lines: Vec<String>) {
@@ -203,7 +962,13 @@ pub fn solution(lines: Vec<String>) {
 mod q16_tests {
     use crate::utilities::read_lines;
 
-    use super::{part_1, part_2};
+    use num_bigint::BigUint;
+
+    use super::{
+        assemble, decompile, detect_a_shift, disassemble, find_all_quines, find_quine, listing,
+        part_1, part_2, AssembleError, Computer, ExecutionError, Opcode, Operand, QuineError,
+        Register, Registers, RunConfig, RunOutcome,
+    };
 
     fn get_lines() -> Vec<String> {
         read_lines("./data/q17_test.txt")
@@ -218,4 +983,303 @@ mod q16_tests {
     fn test_part2() {
         assert_eq!(part_2(&get_lines()), 117440);
     }
+
+    #[test]
+    fn test_disassemble() {
+        let program = vec![0, 3, 5, 4, 3, 0];
+        let instructions = disassemble(&program).unwrap();
+
+        assert_eq!(instructions.len(), 3);
+        assert_eq!(instructions[0].opcode, Opcode::Adv);
+        assert_eq!(instructions[0].operand, Operand::Literal(3));
+        assert_eq!(instructions[1].operand, Operand::A);
+        assert_eq!(instructions[2].address, 4);
+        assert_eq!(
+            listing(&program).unwrap(),
+            "  0: adv 3\n  2: out A\n  4: jnz 0"
+        );
+    }
+
+    #[test]
+    fn test_disassemble_errors() {
+        assert_eq!(
+            disassemble(&[0, 3, 8, 4]),
+            Err(ExecutionError::InvalidOpcode {
+                address: 2,
+                opcode: 8
+            })
+        );
+        assert_eq!(
+            disassemble(&[0, 3, 5]),
+            Err(ExecutionError::MissingOperand { address: 2 })
+        );
+        assert_eq!(
+            detect_a_shift(&[0, 3, 5, 4, 3]),
+            Err(QuineError::Execution(ExecutionError::MissingOperand {
+                address: 4
+            }))
+        );
+    }
+
+    #[test]
+    fn test_disassemble_combo_operands() {
+        let program = vec![2, 4, 1, 5, 7, 5, 4, 3, 6, 6, 5, 5, 3, 0];
+        let listing = listing(&program).unwrap();
+
+        assert_eq!(
+            listing,
+            "  0: bst A\n  2: bxl 5\n  4: cdv B\n  6: bxc 3\n  8: bdv C\n 10: out B\n 12: jnz 0"
+        );
+    }
+
+    #[test]
+    fn test_decompile() {
+        let iteration = decompile(&[0, 3, 5, 4, 3, 0]).unwrap();
+
+        assert_eq!(iteration.jump_target, Some(0));
+        assert_eq!(
+            iteration.to_string(),
+            "out ((A >> 3) % 8)\nA = (A >> 3)\nB = B\nC = C\nif A != 0 goto 0"
+        );
+    }
+
+    #[test]
+    fn test_decompile_substitutes_registers() {
+        let iteration = decompile(&[2, 4, 1, 1, 7, 5, 4, 0, 5, 5, 0, 3, 3, 0]).unwrap();
+
+        assert_eq!(
+            iteration.outputs[0].to_string(),
+            "((((A % 8) ^ 1) ^ (A >> ((A % 8) ^ 1))) % 8)"
+        );
+        assert_eq!(iteration.a.to_string(), "(A >> 3)");
+    }
+
+    #[test]
+    fn test_assemble() {
+        let source = "
+            ; part 2 example
+            loop:
+                adv 3
+                out A
+                jnz loop
+        ";
+
+        assert_eq!(assemble(source), Ok(vec![0, 3, 5, 4, 3, 0]));
+    }
+
+    #[test]
+    fn test_assemble_round_trip() {
+        let programs = [
+            vec![0, 1, 5, 4, 3, 0],
+            vec![0, 3, 5, 4, 3, 0],
+            vec![2, 4, 1, 5, 7, 5, 4, 3, 6, 6, 5, 5, 3, 0],
+            vec![2, 6, 1, 7, 4, 0, 0, 7, 5, 5],
+        ];
+
+        for program in programs {
+            let source = disassemble(&program)
+                .unwrap()
+                .iter()
+                .map(|instruction| instruction.to_string())
+                .collect::<Vec<String>>()
+                .join("\n");
+            assert_eq!(assemble(&source), Ok(program));
+        }
+    }
+
+    #[test]
+    fn test_assemble_errors() {
+        assert_eq!(
+            assemble("adv 3\nfoo 1"),
+            Err(AssembleError::UnknownMnemonic {
+                line: 2,
+                mnemonic: "foo".to_string()
+            })
+        );
+        assert_eq!(
+            assemble("out 5"),
+            Err(AssembleError::InvalidOperand {
+                line: 1,
+                operand: "5".to_string()
+            })
+        );
+        assert_eq!(
+            assemble("jnz end"),
+            Err(AssembleError::UnknownLabel {
+                line: 1,
+                label: "end".to_string()
+            })
+        );
+        assert_eq!(
+            assemble("bxl"),
+            Err(AssembleError::MissingOperand { line: 1 })
+        );
+        assert_eq!(
+            assemble("a: adv 1\na: adv 2"),
+            Err(AssembleError::DuplicateLabel {
+                line: 2,
+                label: "a".to_string()
+            })
+        );
+        assert_eq!(
+            assemble("adv 1\nadv 1\nadv 1\nadv 1\nend: out A\njnz end"),
+            Err(AssembleError::JumpOutOfRange { line: 6, target: 8 })
+        );
+    }
+
+    #[test]
+    fn test_run() {
+        let mut computer = Computer::<u64>::new(729, 0, 0, vec![0, 1, 5, 4, 3, 0]);
+
+        assert_eq!(computer.run(), Ok(RunOutcome::Halted));
+        assert_eq!(computer.get_output(), "4,6,3,5,6,3,5,2,1,0");
+    }
+
+    #[test]
+    fn test_run_step_limit() {
+        let mut computer = Computer::<u64>::new(1, 0, 0, vec![3, 0]);
+        let config = RunConfig::new().with_max_steps(100);
+
+        assert_eq!(
+            computer.run_with(&config),
+            Err(ExecutionError::StepLimitExceeded { steps: 100 })
+        );
+    }
+
+    #[test]
+    fn test_run_invalid_combo_operand() {
+        let mut computer = Computer::<u64>::new(1, 0, 0, vec![2, 4, 5, 7]);
+
+        assert_eq!(
+            computer.run(),
+            Err(ExecutionError::InvalidComboOperand { address: 2 })
+        );
+    }
+
+    #[test]
+    fn test_run_breakpoint() {
+        let mut computer = Computer::<u64>::new(729, 0, 0, vec![0, 1, 5, 4, 3, 0]);
+        let config = RunConfig::new().with_breakpoint(2);
+
+        assert_eq!(computer.run_with(&config), Ok(RunOutcome::Breakpoint(2)));
+        assert!(computer.output.is_empty());
+        assert_eq!(computer.run_with(&config), Ok(RunOutcome::Breakpoint(2)));
+        assert_eq!(computer.output, vec![4]);
+    }
+
+    #[test]
+    fn test_run_trace() {
+        let mut computer = Computer::<u64>::new(10, 0, 0, vec![0, 1, 5, 4]);
+        let config = RunConfig::new().with_trace();
+
+        assert_eq!(computer.run_with(&config), Ok(RunOutcome::Halted));
+        assert_eq!(computer.trace.len(), 2);
+        assert_eq!(computer.trace[0].before, Registers { a: 10, b: 0, c: 0 });
+        assert_eq!(computer.trace[0].after, Registers { a: 5, b: 0, c: 0 });
+        assert_eq!(
+            computer.get_trace(),
+            "     0   0: adv 1  A=10 B=0 C=0 -> A=5 B=0 C=0\n     1   2: out A  A=5 B=0 C=0 -> A=5 B=0 C=0"
+        );
+    }
+
+    #[test]
+    fn test_detect_a_shift() {
+        assert_eq!(detect_a_shift(&[0, 3, 5, 4, 3, 0]), Ok(3));
+        assert_eq!(detect_a_shift(&[0, 1, 5, 4, 3, 0]), Ok(1));
+        assert_eq!(
+            detect_a_shift(&[0, 3, 5, 4, 3, 0, 5, 4]),
+            Err(QuineError::NotSingleLoop)
+        );
+        assert_eq!(
+            detect_a_shift(&[0, 3, 5, 4, 5, 5, 3, 0]),
+            Err(QuineError::OutputsPerIteration(2))
+        );
+        assert!(matches!(
+            detect_a_shift(&[2, 4, 0, 5, 5, 4, 3, 0]),
+            Err(QuineError::VariableShift(_))
+        ));
+    }
+
+    #[test]
+    fn test_find_quine() {
+        let computer = Computer::<u64>::new(2024, 0, 0, vec![0, 3, 5, 4, 3, 0]);
+
+        assert_eq!(find_quine(&computer), Ok(117440));
+    }
+
+    #[test]
+    fn test_find_all_quines() {
+        let computer = Computer::<u64>::new(0, 0, 0, vec![0, 3, 5, 4, 3, 0]);
+        let solutions = find_all_quines(&computer).unwrap();
+
+        assert_eq!(solutions, (117440..117448).collect::<Vec<u64>>());
+        for a in solutions {
+            let mut test_computer = computer.clone();
+            test_computer.a = a;
+            test_computer.run().unwrap();
+            assert_eq!(test_computer.output, computer.program);
+        }
+    }
+
+    #[test]
+    fn test_find_quine_no_solution() {
+        let computer = Computer::<u64>::new(0, 0, 0, vec![0, 3, 1, 1, 5, 5, 3, 0]);
+
+        assert_eq!(find_quine(&computer), Err(QuineError::NoSolution));
+    }
+
+    // adv 3, eight no-op bxl 0, out A, jnz 0: needs 3 bits of A per output, 69 bits in total
+    fn long_quine_program() -> Vec<usize> {
+        let mut program = vec![0, 3];
+        program.extend([1, 0].repeat(8));
+        program.extend([5, 4, 3, 0]);
+        program
+    }
+
+    #[test]
+    fn test_wide_shift() {
+        let mut computer = Computer::<u64>::new(u64::MAX, 70, 0, vec![0, 5, 5, 4]);
+        computer.run().unwrap();
+        assert_eq!(computer.a, 0);
+
+        let mut computer = Computer::<u128>::new(u128::MAX, 70, 0, vec![0, 5, 5, 4]);
+        computer.run().unwrap();
+        assert_eq!(computer.a, u128::MAX >> 70);
+
+        let a = BigUint::from(1_u8) << 200;
+        let b = BigUint::from(198_u8);
+        let mut computer = Computer::new(a, b, BigUint::ZERO, vec![0, 5, 5, 4]);
+        computer.run().unwrap();
+        assert_eq!(computer.a, BigUint::from(4_u8));
+        assert_eq!(computer.output, vec![4]);
+    }
+
+    #[test]
+    fn test_find_quine_wide_registers() {
+        let program = long_quine_program();
+        let expected = program
+            .iter()
+            .rev()
+            .fold(0_u128, |a, &value| (a << 3) | value as u128)
+            << 3;
+
+        let computer = Computer::<u64>::new(0, 0, 0, program.clone());
+        assert_eq!(find_quine(&computer), Err(QuineError::RegisterOverflow));
+
+        let computer = Computer::<u128>::new(0, 0, 0, program.clone());
+        assert_eq!(find_quine(&computer), Ok(expected));
+
+        let computer = Computer::new(BigUint::ZERO, BigUint::ZERO, BigUint::ZERO, program);
+        assert_eq!(find_quine(&computer), Ok(BigUint::from(expected)));
+    }
+
+    #[test]
+    fn test_register_push_digit() {
+        assert_eq!(5_u64.push_digit(3, 2), Some(42));
+        assert_eq!((u64::MAX >> 2).push_digit(3, 0), None);
+        assert_eq!(
+            BigUint::from(u64::MAX).push_digit(3, 7),
+            Some((BigUint::from(u64::MAX) << 3) | BigUint::from(7_u8))
+        );
+    }
 }
@@ -1,80 +1,315 @@
-fn safe_check(levels: Vec<i64>) -> bool {
-    let mut prev_value = 0_i64;
-    let mut increasing: bool = true;
-    for (i, level) in levels.into_iter().enumerate() {
-        if i == 0 {
-            prev_value = level;
-        } else if (1..=3).contains(&(level - prev_value).abs()) {
-            if i == 1 {
-                increasing = prev_value < level;
-            } else if increasing != (level > prev_value) {
-                return false;
+use std::fmt;
+use std::io::{self, BufRead};
+use std::path::Path;
+
+use crate::utilities::{consume_reader_lines, stream_lines};
+
+// What makes a report safe: every step between kept levels changes by min_step..=max_step in
+// one direction, after leaving out at most max_removals levels
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct SafetyRules {
+    min_step: i64,
+    max_step: i64,
+    max_removals: usize,
+}
+
+impl Default for SafetyRules {
+    fn default() -> Self {
+        Self {
+            min_step: 1,
+            max_step: 3,
+            max_removals: 0,
+        }
+    }
+}
+
+impl SafetyRules {
+    fn with_step_bounds(mut self, min_step: i64, max_step: i64) -> Self {
+        self.min_step = min_step;
+        self.max_step = max_step;
+        self.validate();
+        self
+    }
+
+    // Steps are measured along the direction of the report, so they are never negative
+    fn validate(&self) {
+        assert!(
+            0 <= self.min_step && self.min_step <= self.max_step,
+            "invalid step bounds {}..={}",
+            self.min_step,
+            self.max_step
+        );
+    }
+
+    fn with_max_removals(mut self, max_removals: usize) -> Self {
+        self.max_removals = max_removals;
+        self
+    }
+
+    fn allows(&self, from: i64, to: i64, increasing: bool) -> bool {
+        let step = if increasing { to - from } else { from - to };
+        (self.min_step..=self.max_step).contains(&step)
+    }
+}
+
+// Fewest levels to leave out so the rest is safe in one direction. removals[i] is the fewest
+// removals before level i when level i is kept; i can only follow one of the max_removals + 1
+// levels before it, so this takes O(n * max_removals). On ties the later level is kept, so the
+// earliest possible levels are the ones left out.
+fn removals_in_direction(
+    levels: &[i64],
+    rules: &SafetyRules,
+    increasing: bool,
+) -> Option<Vec<usize>> {
+    let window = rules.max_removals + 1;
+    let mut removals: Vec<Option<usize>> = vec![None; levels.len()];
+    let mut previous: Vec<Option<usize>> = vec![None; levels.len()];
+
+    for i in 0..levels.len() {
+        if i <= rules.max_removals {
+            removals[i] = Some(i);
+        }
+        for p in i.saturating_sub(window)..i {
+            let Some(before) = removals[p] else {
+                continue;
+            };
+            let total = before + i - p - 1;
+            if total <= rules.max_removals
+                && removals[i].is_none_or(|best| total <= best)
+                && rules.allows(levels[p], levels[i], increasing)
+            {
+                removals[i] = Some(total);
+                previous[i] = Some(p);
             }
-            prev_value = level;
-            continue;
-        } else {
-            return false;
         }
     }
-    true
+
+    let (mut last, _) = (0..levels.len())
+        .filter_map(|i| Some((i, removals[i]? + levels.len() - 1 - i)))
+        .filter(|(_, total)| *total <= rules.max_removals)
+        .min_by_key(|(_, total)| *total)?;
+
+    let mut kept = vec![false; levels.len()];
+    kept[last] = true;
+    while let Some(p) = previous[last] {
+        kept[p] = true;
+        last = p;
+    }
+    Some((0..levels.len()).filter(|i| !kept[*i]).collect())
+}
+
+// Indices of the levels left out to make the report safe, empty when it is safe as it is
+fn dampened_removals(levels: &[i64], rules: &SafetyRules) -> Option<Vec<usize>> {
+    if levels.is_empty() {
+        return Some(Vec::new());
+    }
+    let increasing = removals_in_direction(levels, rules, true);
+    let decreasing = removals_in_direction(levels, rules, false);
+    match (increasing, decreasing) {
+        (Some(increasing), Some(decreasing)) if decreasing.len() < increasing.len() => {
+            Some(decreasing)
+        }
+        (Some(increasing), _) => Some(increasing),
+        (None, decreasing) => decreasing,
+    }
 }
 
-fn load_reports(lines: Vec<String>) -> Vec<Vec<i64>> {
+fn safe_check(levels: &[i64], rules: &SafetyRules) -> bool {
+    dampened_removals(levels, rules).is_some()
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Failure {
+    DirectionChange,
+    StepOutOfBounds,
+}
+
+// Where a report first breaks the rules, ignoring removals: the index of the offending level
+// and the change from the level before it
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Diagnosis {
+    index: usize,
+    failure: Failure,
+    delta: i64,
+}
+
+impl fmt::Display for Diagnosis {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.failure {
+            Failure::DirectionChange => write!(
+                f,
+                "level {} changes direction by {:+}",
+                self.index, self.delta
+            ),
+            Failure::StepOutOfBounds => write!(
+                f,
+                "level {} steps by {:+}, outside the allowed bounds",
+                self.index, self.delta
+            ),
+        }
+    }
+}
+
+// The direction is set by the first step that moves at all
+fn diagnose(levels: &[i64], rules: &SafetyRules) -> Option<Diagnosis> {
+    let mut increasing = None;
+    for (i, pair) in levels.windows(2).enumerate() {
+        let delta = pair[1] - pair[0];
+        let diagnosis = |failure| Diagnosis {
+            index: i + 1,
+            failure,
+            delta,
+        };
+        if delta != 0 && *increasing.get_or_insert(delta > 0) != (delta > 0) {
+            return Some(diagnosis(Failure::DirectionChange));
+        }
+        if !(rules.min_step..=rules.max_step).contains(&delta.abs()) {
+            return Some(diagnosis(Failure::StepOutOfBounds));
+        }
+    }
+    None
+}
+
+fn parse_report(line: &str) -> Vec<i64> {
+    line.split_whitespace()
+        .map(|x| x.parse::<i64>().unwrap())
+        .collect()
+}
+
+// Reports are checked as they are read, so only one line is held in memory at a time
+fn count_reports<I, S>(lines: I, rules: &SafetyRules) -> i64
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+{
     lines
         .into_iter()
-        .map(|le_string| {
-            le_string
-                .split(" ")
-                .map(|x| x.parse::<i64>().unwrap())
-                .collect()
-        })
-        .collect()
+        .filter(|line| !line.as_ref().trim().is_empty())
+        .filter(|line| safe_check(&parse_report(line.as_ref()), rules))
+        .count() as i64
 }
 
-fn part_1(lines: Vec<String>) -> i64 {
-    let reports: Vec<Vec<i64>> = load_reports(lines);
+fn part_1<I, S>(lines: I) -> i64
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+{
+    count_reports(lines, &SafetyRules::default())
+}
+
+fn part_2<I, S>(lines: I) -> i64
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+{
+    count_reports(lines, &SafetyRules::default().with_max_removals(1))
+}
 
-    let mut safe_counter = 0_i64;
-    for levels in reports {
-        if safe_check(levels) {
-            safe_counter += 1;
+// Safe reports without and with the Problem Dampener, counted one line at a time
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Tally {
+    pub safe: i64,
+    pub dampened: i64,
+}
+
+impl Tally {
+    fn add_line(&mut self, line: &str) {
+        if line.trim().is_empty() {
+            return;
         }
+        let rules = SafetyRules::default();
+        let levels = parse_report(line);
+        self.safe += safe_check(&levels, &rules) as i64;
+        self.dampened += safe_check(&levels, &rules.with_max_removals(1)) as i64;
     }
 
-    safe_counter
+    pub fn from_lines<I, S>(lines: I) -> Tally
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let mut tally = Tally::default();
+        for line in lines {
+            tally.add_line(line.as_ref());
+        }
+        tally
+    }
+
+    pub fn from_reader<R: BufRead>(reader: R) -> io::Result<Tally> {
+        consume_reader_lines(reader, |lines| Tally::from_lines(lines))
+    }
+
+    fn print(&self) {
+        println!("Answer part 1: {}", self.safe);
+        println!("Answer part 2: {}", self.dampened);
+    }
 }
 
-fn part_2(lines: Vec<String>) -> i64 {
-    let reports: Vec<Vec<i64>> = load_reports(lines);
+pub fn solution(lines: Vec<String>) {
+    Tally::from_lines(&lines).print();
+}
 
-    let mut safe_counter = 0_i64;
-    for levels in reports {
-        if safe_check(levels.clone()) {
-            safe_counter += 1;
-        } else {
-            for i in 0..levels.len() {
-                let mut new_levels = levels.clone();
-                new_levels.remove(i);
-                if safe_check(new_levels) {
-                    safe_counter += 1;
-                    break;
-                }
+// Streams the input file, solving both parts in a single pass
+pub fn solution_streaming<P: AsRef<Path>>(filename: P) {
+    Tally::from_lines(stream_lines(filename)).print();
+}
+
+pub fn solution_from_reader<R: BufRead>(reader: R) -> io::Result<()> {
+    Tally::from_reader(reader)?.print();
+    Ok(())
+}
+
+// Reports still unsafe after the allowed removals, with the line number (1-based), the
+// report and why it fails
+fn unsafe_reports<I, S>(lines: I, rules: &SafetyRules) -> Vec<(usize, String, Diagnosis)>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+{
+    // rules built without `with_step_bounds` are checked here, before any report is read
+    rules.validate();
+    lines
+        .into_iter()
+        .enumerate()
+        .filter(|(_, line)| !line.as_ref().trim().is_empty())
+        .filter_map(|(i, line)| {
+            let levels = parse_report(line.as_ref());
+            if safe_check(&levels, rules) {
+                return None;
             }
-        }
+            // with valid step bounds every unsafe report breaks a rule somewhere
+            let diagnosis = diagnose(&levels, rules).expect("unsafe report without a violation");
+            Some((i + 1, line.as_ref().trim().to_string(), diagnosis))
+        })
+        .collect()
+}
+
+pub fn print_unsafe_reports<I, S>(lines: I)
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+{
+    let rules = SafetyRules::default().with_max_removals(1);
+    for (line_number, report, diagnosis) in unsafe_reports(lines, &rules) {
+        println!("line {}: {} ({})", line_number, report, diagnosis);
     }
-    safe_counter
 }
 
-pub fn solution(lines: Vec<String>) {
-    println!("Answer part 1: {}", part_1(lines.clone()));
-    println!("Answer part 2: {}", part_2(lines));
+// Both answers, followed by the reports the Problem Dampener cannot fix
+pub fn solution_with_unsafe_reports(lines: Vec<String>) {
+    Tally::from_lines(&lines).print();
+    println!("Unsafe reports:");
+    print_unsafe_reports(&lines);
 }
 
 #[cfg(test)]
 mod q2_tests {
     use crate::utilities::read_lines;
 
-    use super::{part_1, part_2};
+    use super::{
+        dampened_removals, diagnose, parse_report, part_1, part_2, solution_from_reader,
+        unsafe_reports, Diagnosis, Failure, SafetyRules, Tally,
+    };
 
     fn get_lines() -> Vec<String> {
         read_lines("./data/q2_test.txt")
@@ -89,4 +324,211 @@ mod q2_tests {
     fn test_part2() {
         assert_eq!(part_2(get_lines()), 4);
     }
+
+    fn get_example() -> &'static str {
+        "7 6 4 2 1
+1 2 7 8 9
+9 7 6 2 1
+1 3 2 4 5
+8 6 4 4 1
+1 3 6 7 9
+"
+    }
+
+    #[test]
+    fn test_streaming() {
+        assert_eq!(part_1(get_example().lines()), 2);
+        assert_eq!(part_2(get_example().lines()), 4);
+        let tally = Tally::from_reader(get_example().as_bytes()).unwrap();
+        assert_eq!(
+            tally,
+            Tally {
+                safe: 2,
+                dampened: 4
+            }
+        );
+        assert_eq!(Tally::from_lines(get_example().lines()), tally);
+        solution_from_reader(get_example().as_bytes()).unwrap();
+
+        // generated lazily, never held in memory
+        let lines = (0..200_000).map(|i| match i % 3 {
+            0 => "1 2 3 4 5",
+            1 => "1 2 9 4 5",
+            _ => "1 9 2 9 5",
+        });
+        assert_eq!(part_1(lines.clone()), 66_667);
+        assert_eq!(part_2(lines), 133_334);
+    }
+
+    // Smallest number of removals by trying every subset, for checking the single pass
+    fn brute_force_removals(levels: &[i64], rules: &SafetyRules) -> Option<usize> {
+        (0..1_usize << levels.len())
+            .filter(|mask| mask.count_ones() as usize <= rules.max_removals)
+            .filter(|mask| {
+                let kept: Vec<i64> = (0..levels.len())
+                    .filter(|i| mask & (1 << i) == 0)
+                    .map(|i| levels[i])
+                    .collect();
+                let steps: Vec<i64> = kept.windows(2).map(|pair| pair[1] - pair[0]).collect();
+                [1, -1].into_iter().any(|sign| {
+                    steps
+                        .iter()
+                        .all(|step| (rules.min_step..=rules.max_step).contains(&(sign * step)))
+                })
+            })
+            .map(|mask| mask.count_ones() as usize)
+            .min()
+    }
+
+    #[test]
+    fn test_dampened_removals() {
+        let rules = SafetyRules::default().with_max_removals(1);
+        let removals: Vec<Option<Vec<usize>>> = get_example()
+            .lines()
+            .map(|line| dampened_removals(&parse_report(line), &rules))
+            .collect();
+        assert_eq!(
+            removals,
+            vec![
+                Some(vec![]),
+                None,
+                None,
+                Some(vec![1]),
+                Some(vec![2]),
+                Some(vec![])
+            ]
+        );
+
+        let rules = SafetyRules::default().with_max_removals(2);
+        assert_eq!(
+            dampened_removals(&[1, 9, 2, 9, 3], &rules),
+            Some(vec![1, 3])
+        );
+        assert_eq!(
+            dampened_removals(&[9, 1, 2, 3, 0], &rules),
+            Some(vec![0, 4])
+        );
+        assert_eq!(dampened_removals(&[1, 9, 8, 7, 2, 3], &rules), None);
+
+        let rules = SafetyRules::default().with_step_bounds(2, 5);
+        assert_eq!(dampened_removals(&[1, 3, 8, 10], &rules), Some(vec![]));
+        assert_eq!(dampened_removals(&[1, 2, 4], &rules), None);
+        assert_eq!(
+            dampened_removals(&[1, 2, 4], &rules.with_max_removals(1)),
+            Some(vec![0])
+        );
+    }
+
+    #[test]
+    fn test_against_brute_force() {
+        let mut seed = 12345_u64;
+        let mut next = |modulo: u64| {
+            seed = seed
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (seed >> 33) % modulo
+        };
+        for _ in 0..1000 {
+            let n_levels = next(9) as usize;
+            let levels: Vec<i64> = (0..n_levels).map(|_| next(12) as i64).collect();
+            let rules = SafetyRules::default()
+                .with_step_bounds(next(2) as i64, 1 + next(4) as i64)
+                .with_max_removals(next(4) as usize);
+
+            let removals = dampened_removals(&levels, &rules);
+            assert_eq!(
+                removals.as_ref().map(|removed| removed.len()),
+                brute_force_removals(&levels, &rules),
+                "{:?} {:?}",
+                levels,
+                rules
+            );
+            if let Some(removed) = removals {
+                let kept: Vec<i64> = (0..n_levels)
+                    .filter(|i| !removed.contains(i))
+                    .map(|i| levels[i])
+                    .collect();
+                assert!(dampened_removals(&kept, &rules.with_max_removals(0)) == Some(vec![]));
+            }
+        }
+    }
+
+    #[test]
+    fn test_diagnose() {
+        let rules = SafetyRules::default();
+        let diagnoses: Vec<Option<Diagnosis>> = get_example()
+            .lines()
+            .map(|line| diagnose(&parse_report(line), &rules))
+            .collect();
+        let diagnosis = |index, failure, delta| {
+            Some(Diagnosis {
+                index,
+                failure,
+                delta,
+            })
+        };
+        assert_eq!(
+            diagnoses,
+            vec![
+                None,
+                diagnosis(2, Failure::StepOutOfBounds, 5),
+                diagnosis(3, Failure::StepOutOfBounds, -4),
+                diagnosis(2, Failure::DirectionChange, -1),
+                diagnosis(3, Failure::StepOutOfBounds, 0),
+                None,
+            ]
+        );
+        assert_eq!(
+            diagnosis(3, Failure::DirectionChange, -1)
+                .unwrap()
+                .to_string(),
+            "level 3 changes direction by -1"
+        );
+
+        let rules = SafetyRules::default().with_step_bounds(0, 3);
+        assert_eq!(diagnose(&[4, 4, 5, 5, 7], &rules), None);
+        assert_eq!(
+            diagnose(&[4, 4, 5, 4], &rules),
+            diagnosis(3, Failure::DirectionChange, -1)
+        );
+    }
+
+    #[test]
+    fn test_unsafe_reports() {
+        let rules = SafetyRules::default().with_max_removals(1);
+        let listing: Vec<(usize, String)> = unsafe_reports(get_example().lines(), &rules)
+            .into_iter()
+            .map(|(line_number, _, diagnosis)| (line_number, diagnosis.to_string()))
+            .collect();
+        assert_eq!(
+            listing,
+            vec![
+                (
+                    2,
+                    "level 2 steps by +5, outside the allowed bounds".to_string()
+                ),
+                (
+                    3,
+                    "level 3 steps by -4, outside the allowed bounds".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid step bounds -1..=3")]
+    fn test_negative_step_bounds() {
+        SafetyRules::default().with_step_bounds(-1, 3);
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid step bounds 3..=1")]
+    fn test_unsafe_reports_validates_rules() {
+        let rules = SafetyRules {
+            min_step: 3,
+            max_step: 1,
+            max_removals: 1,
+        };
+        unsafe_reports(get_example().lines(), &rules);
+    }
 }
@@ -1,51 +1,248 @@
-use regex::Regex;
+use std::fmt;
 use std::time::Instant;
 
-fn part_1(lines: &[String]) -> i64 {
-    let mul_re = Regex::new(r"mul\(([0-9]{1,3}),([0-9]{1,3})\)").unwrap();
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct State {
+    enabled: bool,
+    sum: i64,
+}
 
-    let mut result = 0_i64;
-    for (mul_string, [x_str, y_str]) in mul_re
-        .captures_iter(&lines.join(" "))
-        .map(|caps| caps.extract())
-    {
-        result += x_str.parse::<i64>().unwrap() * y_str.parse::<i64>().unwrap();
+impl Default for State {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            sum: 0,
+        }
     }
+}
 
-    result
+// An instruction is written as `name(a,b,..)` with `arity` arguments of one to three digits
+#[derive(Clone, Copy)]
+struct InstructionSpec {
+    name: &'static str,
+    arity: usize,
+    execute: fn(&mut State, &[i64]),
 }
 
-fn part_2(lines: &[String]) -> i64 {
-    let mul_re = Regex::new(r"mul\([0-9]{1,3},[0-9]{1,3}\)|do\(\)|don't\(\)").unwrap();
-    let digits_re = Regex::new(r"([0-9]{1,3}),([0-9]{1,3})").unwrap();
-
-    let mut result = 0_i64;
-    let mut can_multiply = true;
-    for (mul_string, []) in mul_re
-        .captures_iter(&lines.join(" "))
-        .map(|caps| caps.extract())
-    {
-        if mul_string == "do()" {
-            can_multiply = true;
-            continue;
+struct InstructionSet {
+    specs: Vec<InstructionSpec>,
+}
+
+impl InstructionSet {
+    fn new() -> Self {
+        Self { specs: Vec::new() }
+    }
+
+    fn with(mut self, name: &'static str, arity: usize, execute: fn(&mut State, &[i64])) -> Self {
+        self.specs.push(InstructionSpec {
+            name,
+            arity,
+            execute,
+        });
+        self
+    }
+
+    fn execute(&self, state: &mut State, token: &Token) {
+        let spec = self
+            .specs
+            .iter()
+            .find(|spec| spec.name == token.name)
+            .unwrap();
+        (spec.execute)(state, &token.args);
+    }
+
+    fn part_1() -> Self {
+        Self::new().with("mul", 2, |state, args| state.sum += args[0] * args[1])
+    }
+
+    fn part_2() -> Self {
+        Self::new()
+            .with("mul", 2, |state, args| {
+                if state.enabled {
+                    state.sum += args[0] * args[1]
+                }
+            })
+            .with("do", 0, |state, _| state.enabled = true)
+            .with("don't", 0, |state, _| state.enabled = false)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Token {
+    name: &'static str,
+    args: Vec<i64>,
+    // byte range of the instruction in the memory
+    offset: usize,
+    len: usize,
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let args: Vec<String> = self.args.iter().map(|arg| arg.to_string()).collect();
+        write!(f, "{}({})", self.name, args.join(","))
+    }
+}
+
+// Reads an argument of one to three digits, returning it with the bytes it takes
+fn scan_number(bytes: &[u8]) -> Option<(i64, usize)> {
+    let n_digits = bytes
+        .iter()
+        .take(4)
+        .take_while(|byte| byte.is_ascii_digit())
+        .count();
+    if !(1..=3).contains(&n_digits) {
+        return None;
+    }
+    let value = bytes[..n_digits]
+        .iter()
+        .fold(0, |value, digit| value * 10 + (digit - b'0') as i64);
+    Some((value, n_digits))
+}
+
+// Length of `name(args)` when the bytes start with a well-formed instruction
+fn scan_instruction(bytes: &[u8], spec: &InstructionSpec) -> Option<(Vec<i64>, usize)> {
+    let mut position = spec.name.len() + 1;
+    if !bytes.starts_with(spec.name.as_bytes()) || bytes.get(position - 1) != Some(&b'(') {
+        return None;
+    }
+    let mut args = Vec::with_capacity(spec.arity);
+    for i in 0..spec.arity {
+        if i > 0 {
+            if bytes.get(position) != Some(&b',') {
+                return None;
+            }
+            position += 1;
         }
-        if mul_string == "don't()" {
-            can_multiply = false;
-            continue;
+        let (arg, n_digits) = scan_number(&bytes[position..])?;
+        args.push(arg);
+        position += n_digits;
+    }
+    (bytes.get(position) == Some(&b')')).then_some((args, position + 1))
+}
+
+// Finds every instruction of the set in the corrupted memory, left to right without overlaps
+fn tokenize(memory: &str, instructions: &InstructionSet) -> Vec<Token> {
+    let bytes = memory.as_bytes();
+    let mut tokens = Vec::new();
+    let mut offset = 0;
+
+    while offset < bytes.len() {
+        let token = instructions.specs.iter().find_map(|spec| {
+            let (args, len) = scan_instruction(&bytes[offset..], spec)?;
+            Some(Token {
+                name: spec.name,
+                args,
+                offset,
+                len,
+            })
+        });
+        match token {
+            Some(token) => {
+                offset += token.len;
+                tokens.push(token);
+            }
+            None => offset += 1,
         }
+    }
+    tokens
+}
+
+fn interpret(tokens: &[Token], instructions: &InstructionSet) -> State {
+    let mut state = State::default();
+    for token in tokens {
+        instructions.execute(&mut state, token);
+    }
+    state
+}
 
-        if can_multiply {
-            let Some((_, [x_str, y_str])) =
-                digits_re.captures(mul_string).map(|caps| caps.extract())
-            else {
-                println!("Oops, something went wrong");
-                return 0;
-            };
-            result += x_str.parse::<i64>().unwrap() * y_str.parse::<i64>().unwrap();
+// How a token played out: whether it ran while enabled and what it added to the sum
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Step {
+    token: Token,
+    enabled: bool,
+    contribution: i64,
+}
+
+fn trace(tokens: &[Token], instructions: &InstructionSet) -> Vec<Step> {
+    let mut state = State::default();
+    tokens
+        .iter()
+        .map(|token| {
+            let before = state;
+            instructions.execute(&mut state, token);
+            Step {
+                token: token.clone(),
+                enabled: before.enabled,
+                contribution: state.sum - before.sum,
+            }
+        })
+        .collect()
+}
+
+const GREEN: &str = "\x1b[32m";
+const GREY: &str = "\x1b[90m";
+const RESET: &str = "\x1b[0m";
+
+// The memory with every instruction coloured, green when it ran enabled and grey otherwise
+fn annotate(memory: &str, steps: &[Step]) -> String {
+    let mut annotated = String::with_capacity(memory.len());
+    let mut offset = 0;
+    for step in steps {
+        let (start, end) = (step.token.offset, step.token.offset + step.token.len);
+        let colour = if step.enabled { GREEN } else { GREY };
+        annotated.push_str(&memory[offset..start]);
+        annotated.push_str(colour);
+        annotated.push_str(&memory[start..end]);
+        annotated.push_str(RESET);
+        offset = end;
+    }
+    annotated.push_str(&memory[offset..]);
+    annotated
+}
+
+fn instruction_table(memory: &str, steps: &[Step]) -> String {
+    let mut table = format!(
+        "{:>6} {:>9} {:<12} {:<8} {:<7} {:>12}\n",
+        "offset", "line:col", "instruction", "operands", "enabled", "contribution"
+    );
+    // tokens come in order, so lines are counted in a single pass over the memory
+    let (mut scanned, mut line, mut line_start) = (0, 1, 0);
+    for step in steps {
+        let offset = step.token.offset;
+        for (i, byte) in memory.as_bytes()[scanned..offset].iter().enumerate() {
+            if *byte == b'\n' {
+                line += 1;
+                line_start = scanned + i + 1;
+            }
         }
+        scanned = offset;
+        let column = offset - line_start + 1;
+        let operands: Vec<String> = step.token.args.iter().map(|arg| arg.to_string()).collect();
+        table.push_str(&format!(
+            "{:>6} {:>9} {:<12} {:<8} {:<7} {:>12}\n",
+            step.token.offset,
+            format!("{}:{}", line, column),
+            step.token.name,
+            operands.join(","),
+            if step.enabled { "yes" } else { "no" },
+            step.contribution
+        ));
     }
+    table
+}
 
-    result
+fn run(lines: &[String], instructions: &InstructionSet) -> i64 {
+    let memory = lines.join("\n");
+    let tokens = tokenize(&memory, instructions);
+    interpret(&tokens, instructions).sum
+}
+
+fn part_1(lines: &[String]) -> i64 {
+    run(lines, &InstructionSet::part_1())
+}
+
+fn part_2(lines: &[String]) -> i64 {
+    run(lines, &InstructionSet::part_2())
 }
 
 pub fn solution(lines: Vec<String>) {
@@ -64,11 +261,29 @@ pub fn solution(lines: Vec<String>) {
     println!("duration: {:?}", end - start);
 }
 
+// Prints the memory with the instructions of part 2 highlighted, followed by a table of them
+pub fn print_annotated(lines: Vec<String>) {
+    let memory = lines.join("\n");
+    let instructions = InstructionSet::part_2();
+    let steps = trace(&tokenize(&memory, &instructions), &instructions);
+
+    println!("{}", annotate(&memory, &steps));
+    println!();
+    print!("{}", instruction_table(&memory, &steps));
+    println!(
+        "sum: {}",
+        steps.iter().map(|step| step.contribution).sum::<i64>()
+    );
+}
+
 #[cfg(test)]
 mod q3_tests {
     use crate::utilities::read_lines;
 
-    use super::{part_1, part_2};
+    use super::{
+        annotate, instruction_table, interpret, part_1, part_2, tokenize, trace, InstructionSet,
+        Token,
+    };
 
     fn get_lines() -> Vec<String> {
         read_lines("./data/q3_test.txt")
@@ -83,4 +298,124 @@ mod q3_tests {
     fn test_part2() {
         assert_eq!(part_2(&get_lines()), 48);
     }
+
+    const EXAMPLE_1: &str =
+        "xmul(2,4)%&mul[3,7]!@^do_not_mul(5,5)+mul(32,64]then(mul(11,8)mul(8,5))";
+    const EXAMPLE_2: &str =
+        "xmul(2,4)&mul[3,7]!^don't()_mul(5,5)+mul(32,64](mul(11,8)undo()?mul(8,5))";
+
+    #[test]
+    fn test_examples() {
+        assert_eq!(part_1(&[EXAMPLE_1.to_string()]), 161);
+        assert_eq!(part_2(&[EXAMPLE_2.to_string()]), 48);
+        // instructions never span lines
+        assert_eq!(part_1(&["mul(2,".to_string(), "4)mul(1,1)".to_string()]), 1);
+    }
+
+    #[test]
+    fn test_tokenize() {
+        let tokens = tokenize(EXAMPLE_2, &InstructionSet::part_2());
+        let summary: Vec<(String, usize)> = tokens
+            .iter()
+            .map(|token| (token.to_string(), token.offset))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("mul(2,4)".to_string(), 1),
+                ("don't()".to_string(), 20),
+                ("mul(5,5)".to_string(), 28),
+                ("mul(11,8)".to_string(), 48),
+                ("do()".to_string(), 59),
+                ("mul(8,5)".to_string(), 64),
+            ]
+        );
+        assert_eq!(&EXAMPLE_2[48..48 + tokens[3].len], "mul(11,8)");
+
+        let tokens = tokenize(
+            "mul(1234,5)mul(12,34 )mul(,1)mul(999,0)",
+            &InstructionSet::part_1(),
+        );
+        assert_eq!(
+            tokens,
+            vec![Token {
+                name: "mul",
+                args: vec![999, 0],
+                offset: 29,
+                len: 10,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_custom_instructions() {
+        let instructions = InstructionSet::part_2()
+            .with("add", 1, |state, args| {
+                if state.enabled {
+                    state.sum += args[0]
+                }
+            })
+            .with("reset", 0, |state, _| state.sum = 0);
+        let memory = "mul(2,3)add(4)reset()add(5)don't()add(6)do()mul(1,2)add(1,2)";
+        let tokens = tokenize(memory, &instructions);
+        assert_eq!(tokens.len(), 8);
+        assert_eq!(interpret(&tokens, &instructions).sum, 7);
+    }
+
+    #[test]
+    fn test_trace() {
+        let instructions = InstructionSet::part_2();
+        let steps = trace(&tokenize(EXAMPLE_2, &instructions), &instructions);
+        let summary: Vec<(bool, i64)> = steps
+            .iter()
+            .map(|step| (step.enabled, step.contribution))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (true, 8),
+                (true, 0),
+                (false, 0),
+                (false, 0),
+                (false, 0),
+                (true, 40)
+            ]
+        );
+
+        let memory = "ado()bmul(2,3)don't()mul(4,5)c";
+        let steps = trace(&tokenize(memory, &instructions), &instructions);
+        let annotated = annotate(memory, &steps);
+        assert_eq!(
+            annotated,
+            "a\x1b[32mdo()\x1b[0mb\x1b[32mmul(2,3)\x1b[0m\x1b[32mdon't()\x1b[0m\x1b[90mmul(4,5)\x1b[0mc"
+        );
+    }
+
+    #[test]
+    fn test_instruction_table() {
+        let memory = "mul(2,3)\ndon't()mul(4,5)";
+        let instructions = InstructionSet::part_2();
+        let steps = trace(&tokenize(memory, &instructions), &instructions);
+        let table = instruction_table(memory, &steps);
+        let rows: Vec<Vec<&str>> = table
+            .lines()
+            .map(|row| row.split_whitespace().collect())
+            .collect();
+        assert_eq!(
+            rows,
+            vec![
+                vec![
+                    "offset",
+                    "line:col",
+                    "instruction",
+                    "operands",
+                    "enabled",
+                    "contribution"
+                ],
+                vec!["0", "1:1", "mul", "2,3", "yes", "6"],
+                vec!["9", "2:1", "don't", "yes", "0"],
+                vec!["16", "2:8", "mul", "4,5", "no", "0"],
+            ]
+        );
+    }
 }
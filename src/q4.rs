@@ -1,151 +1,371 @@
+use rayon::prelude::*;
+use std::collections::HashMap;
+use std::ops::Range;
 use std::time::Instant;
 
-#[derive(Debug)]
+const ALL_DIRECTIONS: [Direction; 8] = [
+    Direction::Right,
+    Direction::DownRight,
+    Direction::Down,
+    Direction::DownLeft,
+    Direction::Left,
+    Direction::UpLeft,
+    Direction::Up,
+    Direction::UpRight,
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 struct Point {
     x: usize,
     y: usize,
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum Direction {
+    Right,
+    DownRight,
+    Down,
+    DownLeft,
+    Left,
+    UpLeft,
+    Up,
+    UpRight,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum Orientation {
+    Identity,
+    Rotate90,
+    Rotate180,
+    Rotate270,
+    FlipHorizontal,
+    FlipVertical,
+    Transpose,
+    AntiTranspose,
+}
+
+const ALL_ORIENTATIONS: [Orientation; 8] = [
+    Orientation::Identity,
+    Orientation::Rotate90,
+    Orientation::Rotate180,
+    Orientation::Rotate270,
+    Orientation::FlipHorizontal,
+    Orientation::FlipVertical,
+    Orientation::Transpose,
+    Orientation::AntiTranspose,
+];
+
+// Letters stored row by row in one contiguous buffer. Only ASCII grids are supported.
+struct Grid {
+    bytes: Vec<u8>,
+    width: usize,
+    height: usize,
+}
+
+// 2D pattern where `None` is a wildcard that matches any letter
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Stencil {
+    cells: Vec<Vec<Option<u8>>>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct StencilMatch {
+    position: Point,
+    orientation: Orientation,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct WordMatch {
+    word: String,
+    start: Point,
+    direction: Direction,
+}
+
 impl Point {
     fn new(x: usize, y: usize) -> Self {
         Self { x, y }
     }
+}
 
-    fn add_step(&self, step: usize) -> Self {
-        Self {
-            x: self.x + step,
-            y: self.y + step,
+impl Direction {
+    fn step(&self) -> (isize, isize) {
+        match self {
+            Direction::Right => (1, 0),
+            Direction::DownRight => (1, 1),
+            Direction::Down => (0, 1),
+            Direction::DownLeft => (-1, 1),
+            Direction::Left => (-1, 0),
+            Direction::UpLeft => (-1, -1),
+            Direction::Up => (0, -1),
+            Direction::UpRight => (1, -1),
         }
     }
+}
 
-    fn in_bounds(&self, max_x: usize, max_y: usize) -> bool {
-        if self.x > max_x || self.y > max_y {
-            return false;
+impl Stencil {
+    // Rows are separated by `/` or new lines and `.` is a wildcard, e.g. `M.S/.A./M.S`
+    fn parse(text: &str) -> Self {
+        let mut cells: Vec<Vec<Option<u8>>> = text
+            .split(['/', '\n'])
+            .map(|row| row.trim())
+            .filter(|row| !row.is_empty())
+            .map(|row| {
+                row.bytes()
+                    .map(|c| if c == b'.' { None } else { Some(c) })
+                    .collect()
+            })
+            .collect();
+        let width = cells.iter().map(|row| row.len()).max().unwrap_or(0);
+        for row in cells.iter_mut() {
+            row.resize(width, None);
         }
-        true
+        Self { cells }
     }
 
-    fn get_diag_points(&self) -> [Self; 4] {
-        [
-            Self {
-                x: self.x - 1,
-                y: self.y - 1,
-            },
-            Self {
-                x: self.x + 1,
-                y: self.y - 1,
-            },
-            Self {
-                x: self.x - 1,
-                y: self.y + 1,
-            },
-            Self {
-                x: self.x + 1,
-                y: self.y + 1,
-            },
-        ]
+    fn height(&self) -> usize {
+        self.cells.len()
     }
-}
 
-fn transpose(lines: &[String]) -> Vec<String> {
-    assert!(!lines.is_empty());
-    (0..lines[0].len())
-        .map(|i| {
-            lines
-                .iter()
-                .map(|inner| inner.chars().rev().nth(i).unwrap())
-                .collect::<String>()
+    fn width(&self) -> usize {
+        self.cells.first().map_or(0, |row| row.len())
+    }
+
+    fn transformed(&self, orientation: &Orientation) -> Self {
+        let (height, width) = (self.height(), self.width());
+        let (new_height, new_width) = match orientation {
+            Orientation::Identity
+            | Orientation::Rotate180
+            | Orientation::FlipHorizontal
+            | Orientation::FlipVertical => (height, width),
+            _ => (width, height),
+        };
+        let mut cells = vec![vec![None; new_width]; new_height];
+
+        for (y, row) in self.cells.iter().enumerate() {
+            for (x, cell) in row.iter().enumerate() {
+                let (new_x, new_y) = match orientation {
+                    Orientation::Identity => (x, y),
+                    Orientation::Rotate90 => (height - 1 - y, x),
+                    Orientation::Rotate180 => (width - 1 - x, height - 1 - y),
+                    Orientation::Rotate270 => (y, width - 1 - x),
+                    Orientation::FlipHorizontal => (width - 1 - x, y),
+                    Orientation::FlipVertical => (x, height - 1 - y),
+                    Orientation::Transpose => (y, x),
+                    Orientation::AntiTranspose => (height - 1 - y, width - 1 - x),
+                };
+                cells[new_y][new_x] = *cell;
+            }
+        }
+
+        Self { cells }
+    }
+
+    fn matches_at(&self, grid: &Grid, position: Point) -> bool {
+        if position.x + self.width() > grid.width || position.y + self.height() > grid.height {
+            return false;
+        }
+        self.cells.iter().enumerate().all(|(dy, row)| {
+            row.iter().enumerate().all(|(dx, cell)| match cell {
+                None => true,
+                Some(letter) => grid.get(position.x + dx, position.y + dy) == *letter,
+            })
         })
-        .collect()
+    }
 }
 
-fn count_xmas(line: &String, backwards: bool) -> i64 {
-    let xmas = if backwards { "SAMX" } else { "XMAS" };
-    line.as_bytes()
-        .windows(xmas.len())
-        .filter(|&w| w == xmas.as_bytes())
-        .count() as i64
-}
+// Matches the stencil under all rotations and reflections. Orientations that produce the
+// same pattern as an earlier one are skipped, so a symmetric stencil reports each hit once.
+fn match_stencil(lines: &[String], stencil: &Stencil) -> Vec<StencilMatch> {
+    let grid = Grid::from_lines(lines);
+    let mut variants: Vec<(Orientation, Stencil)> = Vec::new();
+    for orientation in ALL_ORIENTATIONS {
+        let variant = stencil.transformed(&orientation);
+        if !variants.iter().any(|(_, existing)| *existing == variant) {
+            variants.push((orientation, variant));
+        }
+    }
 
-fn part_1(lines: &Vec<String>) -> i64 {
-    let mut total_xmas = 0_i64;
-    for line in lines {
-        total_xmas += count_xmas(line, false);
-        total_xmas += count_xmas(line, true);
+    let mut matches: Vec<StencilMatch> = Vec::new();
+    for y in 0..grid.height {
+        for x in 0..grid.width {
+            let position = Point::new(x, y);
+            for (orientation, variant) in variants.iter() {
+                if variant.matches_at(&grid, position) {
+                    matches.push(StencilMatch {
+                        position,
+                        orientation: *orientation,
+                    });
+                }
+            }
+        }
     }
-    let transposed_lines = transpose(lines);
-    for line in &transposed_lines {
-        total_xmas += count_xmas(line, false);
-        total_xmas += count_xmas(line, true);
+
+    matches
+}
+
+impl Grid {
+    fn new(bytes: Vec<u8>, width: usize) -> Self {
+        assert!(
+            width > 0 && bytes.len().is_multiple_of(width),
+            "grid is not rectangular"
+        );
+        let height = bytes.len() / width;
+        Self {
+            bytes,
+            width,
+            height,
+        }
     }
 
-    // damn diagonals
-    let max_x = lines[0].len() - 1;
-    let max_y = lines.len() - 1;
+    fn from_lines(lines: &[String]) -> Self {
+        let width = lines[0].len();
+        let mut bytes: Vec<u8> = Vec::with_capacity(width * lines.len());
+        for line in lines {
+            assert_eq!(line.len(), width, "grid is not rectangular");
+            bytes.extend_from_slice(line.as_bytes());
+        }
+        Self::new(bytes, width)
+    }
 
-    let mut starting_points: Vec<Point> = (0..=max_x).map(|x| Point::new(x, 0)).collect();
-    starting_points.append(
-        &mut (1..=max_x)
-            .map(|y| Point::new(0, y))
-            .collect::<Vec<Point>>(),
-    );
+    fn get(&self, x: usize, y: usize) -> u8 {
+        self.bytes[y * self.width + x]
+    }
 
-    for starting_point in starting_points {
-        let mut diag_chars: Vec<char> = Vec::new();
-        let mut transposed_diag_chars: Vec<char> = Vec::new();
+    // Columns in row `y` where a word of `len` letters read in `direction` stays on the grid
+    fn start_columns(&self, y: usize, len: usize, direction: &Direction) -> Range<usize> {
+        let (dx, dy) = direction.step();
+        let span = len - 1;
+        let fits_vertically = match dy {
+            1 => y + span < self.height,
+            -1 => y >= span,
+            _ => true,
+        };
+        if !fits_vertically || span >= self.width && dx != 0 {
+            return 0..0;
+        }
+        match dx {
+            1 => 0..self.width - span,
+            -1 => span..self.width,
+            _ => 0..self.width,
+        }
+    }
 
-        let mut step = 0_usize;
-        loop {
-            let point = starting_point.add_step(step);
-            if !point.in_bounds(max_x, max_y) {
-                break;
+    // Checks the word cell by cell, stepping through the buffer by a fixed stride.
+    // The start must come from `start_columns`, so no step can leave the grid.
+    fn word_at(&self, word: &[u8], x: usize, y: usize, direction: &Direction) -> bool {
+        let (dx, dy) = direction.step();
+        let stride = dy * self.width as isize + dx;
+        let mut index = y * self.width + x;
+        for letter in word {
+            if self.bytes[index] != *letter {
+                return false;
             }
-            diag_chars.push(lines[point.x].chars().nth(point.y).unwrap());
-            transposed_diag_chars.push(transposed_lines[point.x].chars().nth(point.y).unwrap());
-            step += 1;
+            index = index.wrapping_add_signed(stride);
         }
-        let diag_string: String = diag_chars.into_iter().collect();
-        let transposed_diag_string: String = transposed_diag_chars.into_iter().collect();
-
-        total_xmas += count_xmas(&diag_string, false);
-        total_xmas += count_xmas(&diag_string, true);
-        total_xmas += count_xmas(&transposed_diag_string, false);
-        total_xmas += count_xmas(&transposed_diag_string, true);
+        true
     }
 
-    total_xmas
-}
+    fn find_words_in_row(
+        &self,
+        y: usize,
+        words: &[&str],
+        directions: &[Direction],
+    ) -> Vec<WordMatch> {
+        let candidates: Vec<(&str, &Direction, Range<usize>)> = words
+            .iter()
+            .filter(|word| !word.is_empty())
+            .flat_map(|word| {
+                directions.iter().map(move |direction| {
+                    (
+                        *word,
+                        direction,
+                        self.start_columns(y, word.len(), direction),
+                    )
+                })
+            })
+            .collect();
 
-fn part_2(lines: &[String]) -> i64 {
-    let mut total_x_mas = 0_i64;
-    let max_x = lines[0].len() - 1;
-    let max_y = lines.len() - 1;
-
-    for (y, line) in lines[1..=max_y].iter().enumerate() {
-        for (x, char) in line[1..=max_x].chars().enumerate() {
-            let a_point = Point::new(x + 1, y + 1);
-            if char == 'A' {
-                let points = a_point.get_diag_points();
-                if points.iter().any(|point| !point.in_bounds(max_x, max_y)) {
-                    continue;
+        let mut matches: Vec<WordMatch> = Vec::new();
+        for x in 0..self.width {
+            for (word, direction, columns) in candidates.iter() {
+                if columns.contains(&x) && self.word_at(word.as_bytes(), x, y, direction) {
+                    matches.push(WordMatch {
+                        word: word.to_string(),
+                        start: Point::new(x, y),
+                        direction: **direction,
+                    });
                 }
+            }
+        }
+        matches
+    }
 
-                let letters: Vec<char> = points
-                    .iter()
-                    .map(|point| lines[point.y].chars().nth(point.x).unwrap())
-                    .collect();
-
-                if letters.iter().filter(|letter| **letter == 'M').count() == 2
-                    && letters.iter().filter(|letter| **letter == 'S').count() == 2
-                    && letters[0] != letters[3]
-                {
-                    total_x_mas += 1;
-                }
+    fn count_words_in_row(&self, y: usize, words: &[&str], directions: &[Direction]) -> usize {
+        let mut count = 0;
+        for word in words.iter().filter(|word| !word.is_empty()) {
+            let word = word.as_bytes();
+            for direction in directions {
+                count += self
+                    .start_columns(y, word.len(), direction)
+                    .filter(|x| self.word_at(word, *x, y, direction))
+                    .count();
             }
         }
+        count
+    }
+
+    // Finds every occurrence of every word, read in any of the given directions.
+    // A palindrome is reported once per direction it can be read in.
+    fn find_words(
+        &self,
+        words: &[&str],
+        directions: &[Direction],
+        parallel: bool,
+    ) -> Vec<WordMatch> {
+        if parallel {
+            (0..self.height)
+                .into_par_iter()
+                .flat_map_iter(|y| self.find_words_in_row(y, words, directions))
+                .collect()
+        } else {
+            (0..self.height)
+                .flat_map(|y| self.find_words_in_row(y, words, directions))
+                .collect()
+        }
+    }
+
+    fn count_words(&self, words: &[&str], directions: &[Direction], parallel: bool) -> usize {
+        if parallel {
+            (0..self.height)
+                .into_par_iter()
+                .map(|y| self.count_words_in_row(y, words, directions))
+                .sum()
+        } else {
+            (0..self.height)
+                .map(|y| self.count_words_in_row(y, words, directions))
+                .sum()
+        }
+    }
+}
+
+fn find_words(lines: &[String], words: &[&str], directions: &[Direction]) -> Vec<WordMatch> {
+    Grid::from_lines(lines).find_words(words, directions, false)
+}
+
+fn count_by_direction(matches: &[WordMatch]) -> HashMap<Direction, usize> {
+    let mut counts: HashMap<Direction, usize> = HashMap::new();
+    for word_match in matches {
+        *counts.entry(word_match.direction).or_insert(0) += 1;
     }
+    counts
+}
 
-    total_x_mas
+fn part_1(lines: &[String]) -> i64 {
+    Grid::from_lines(lines).count_words(&["XMAS"], &ALL_DIRECTIONS, false) as i64
+}
+
+fn part_2(lines: &[String]) -> i64 {
+    match_stencil(lines, &Stencil::parse("M.S/.A./M.S")).len() as i64
 }
 
 pub fn solution(lines: Vec<String>) {
@@ -168,12 +388,35 @@ pub fn solution(lines: Vec<String>) {
 mod q4_tests {
     use crate::utilities::read_lines;
 
-    use super::{part_1, part_2};
+    use std::time::Instant;
+
+    use super::{
+        count_by_direction, find_words, match_stencil, part_1, part_2, Direction, Grid,
+        Orientation, Point, Stencil, ALL_DIRECTIONS,
+    };
 
     fn get_lines() -> Vec<String> {
         read_lines("./data/q4_test.txt")
     }
 
+    fn get_example() -> Vec<String> {
+        [
+            "MMMSXXMASM",
+            "MSAMXMSMSA",
+            "AMXSXMAAMM",
+            "MSAMASMSMX",
+            "XMASAMXAMM",
+            "XXAMMXXAMA",
+            "SMSMSASXSS",
+            "SAXAMASAAA",
+            "MAMMMXMMMM",
+            "MXMXAXMASX",
+        ]
+        .iter()
+        .map(|line| line.to_string())
+        .collect()
+    }
+
     #[test]
     fn test_part1() {
         assert_eq!(part_1(&get_lines()), 18);
@@ -183,4 +426,140 @@ mod q4_tests {
     fn test_part2() {
         assert_eq!(part_2(&get_lines()), 9);
     }
+
+    #[test]
+    fn test_find_words_example() {
+        let matches = find_words(&get_example(), &["XMAS"], &ALL_DIRECTIONS);
+        let counts = count_by_direction(&matches);
+
+        assert_eq!(matches.len(), 18);
+        assert_eq!(counts[&Direction::Right], 3);
+        assert_eq!(counts[&Direction::Left], 2);
+        assert_eq!(counts[&Direction::Down], 1);
+        assert_eq!(counts[&Direction::Up], 2);
+        assert_eq!(counts[&Direction::DownRight], 1);
+        assert_eq!(counts[&Direction::DownLeft], 1);
+        assert_eq!(counts[&Direction::UpLeft], 4);
+        assert_eq!(counts[&Direction::UpRight], 4);
+    }
+
+    #[test]
+    fn test_find_words_positions() {
+        let lines = vec!["CAT".to_string(), "AXA".to_string(), "TAC".to_string()];
+        let matches = find_words(&lines, &["CAT", "AX"], &[Direction::Right, Direction::Down]);
+
+        let found: Vec<(&str, Point, Direction)> = matches
+            .iter()
+            .map(|m| (m.word.as_str(), m.start, m.direction))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                ("CAT", Point::new(0, 0), Direction::Right),
+                ("CAT", Point::new(0, 0), Direction::Down),
+                ("AX", Point::new(1, 0), Direction::Down),
+                ("AX", Point::new(0, 1), Direction::Right),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_match_stencil_example() {
+        let matches = match_stencil(&get_example(), &Stencil::parse("M.S/.A./M.S"));
+
+        assert_eq!(matches.len(), 9);
+        assert!(matches
+            .iter()
+            .all(|m| m.orientation == Orientation::Identity
+                || m.orientation == Orientation::Rotate90
+                || m.orientation == Orientation::Rotate180
+                || m.orientation == Orientation::Rotate270));
+    }
+
+    #[test]
+    fn test_match_stencil_orientations() {
+        let lines = vec!["AB.".to_string(), "..B".to_string(), "BA.".to_string()];
+        let matches = match_stencil(&lines, &Stencil::parse("AB"));
+
+        let found: Vec<(Point, Orientation)> = matches
+            .iter()
+            .map(|m| (m.position, m.orientation))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (Point::new(0, 0), Orientation::Identity),
+                (Point::new(0, 2), Orientation::Rotate180),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_stencil_transformed() {
+        let stencil = Stencil::parse("AB.\nC");
+
+        assert_eq!(
+            stencil.transformed(&Orientation::Rotate90),
+            Stencil::parse("CA/.B/..")
+        );
+        assert_eq!(
+            stencil.transformed(&Orientation::Transpose),
+            Stencil::parse("AC/B./..")
+        );
+        assert_eq!(
+            stencil.transformed(&Orientation::FlipHorizontal),
+            Stencil::parse(".BA/..C")
+        );
+        assert_eq!(
+            stencil.transformed(&Orientation::AntiTranspose),
+            Stencil::parse("../.B/CA")
+        );
+    }
+
+    #[test]
+    fn test_grid_parallel_matches_serial() {
+        let grid = Grid::from_lines(&get_example());
+        let words = ["XMAS", "MAS", "AA"];
+
+        let serial = grid.find_words(&words, &ALL_DIRECTIONS, false);
+        let parallel = grid.find_words(&words, &ALL_DIRECTIONS, true);
+
+        assert_eq!(serial, parallel);
+        assert_eq!(
+            grid.count_words(&words, &ALL_DIRECTIONS, false),
+            serial.len()
+        );
+        assert_eq!(
+            grid.count_words(&words, &ALL_DIRECTIONS, true),
+            serial.len()
+        );
+        assert_eq!(grid.count_words(&["XMAS"], &ALL_DIRECTIONS, true), 18);
+    }
+
+    // cargo test --release bench_synthetic_grid -- --ignored --nocapture
+    #[test]
+    #[ignore]
+    fn bench_synthetic_grid() {
+        let size = 10_000;
+        let mut seed = 42_u64;
+        let bytes: Vec<u8> = (0..size * size)
+            .map(|_| {
+                seed = seed
+                    .wrapping_mul(6364136223846793005)
+                    .wrapping_add(1442695040888963407);
+                b"XMAS"[(seed >> 62) as usize]
+            })
+            .collect();
+        let grid = Grid::new(bytes, size);
+
+        let start = Instant::now();
+        let serial = grid.count_words(&["XMAS"], &ALL_DIRECTIONS, false);
+        println!("serial: {} matches in {:?}", serial, start.elapsed());
+
+        let start = Instant::now();
+        let parallel = grid.count_words(&["XMAS"], &ALL_DIRECTIONS, true);
+        println!("parallel: {} matches in {:?}", parallel, start.elapsed());
+
+        assert_eq!(serial, parallel);
+    }
 }
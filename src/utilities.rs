@@ -9,12 +9,32 @@ use std::path::Path;
 // }
 
 pub fn read_lines<P>(filename: P) -> Vec<String>
+where
+    P: AsRef<Path>,
+{
+    stream_lines(filename).collect()
+}
+
+// Lines of a file read one at a time, for inputs too large to hold in memory
+pub fn stream_lines<P>(filename: P) -> impl Iterator<Item = String>
 where
     P: AsRef<Path>,
 {
     let file = File::open(filename).unwrap();
-    io::BufReader::new(file)
+    io::BufReader::new(file).lines().map(|line| line.unwrap())
+}
+
+// Hands the lines of a reader to `consume`, stopping at the first read error and returning it
+pub fn consume_reader_lines<R, T, F>(reader: R, consume: F) -> io::Result<T>
+where
+    R: BufRead,
+    F: FnOnce(&mut dyn Iterator<Item = String>) -> T,
+{
+    let mut error = None;
+    let mut lines = reader
         .lines()
-        .map(|line| line.unwrap())
-        .collect()
+        .map_while(|line| line.map_err(|read_error| error = Some(read_error)).ok());
+    let result = consume(&mut lines);
+    drop(lines);
+    error.map_or(Ok(result), Err)
 }